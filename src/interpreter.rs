@@ -1,2 +1,9 @@
+pub mod call_frame;
+pub mod control_flow;
 pub mod environment;
 pub mod interpreter;
+pub mod iterator;
+pub mod lox_function;
+pub mod modules;
+pub mod operations;
+pub mod runtime_error;
@@ -0,0 +1,37 @@
+use std::{fmt, path::Path};
+
+/// One active call, on the interpreter's stack while the callee runs.
+#[derive(Debug, Clone)]
+pub struct CallFrame {
+    //How the callee shows in a traceback, like "pop()" or "<module util>"
+    pub function: String,
+    //Line of the call site
+    pub line: usize,
+    //File holding the call site, None outside of any file
+    pub file: Option<String>,
+}
+
+impl CallFrame {
+    pub fn new(function: String, line: usize, script: Option<&Path>) -> Self {
+        CallFrame {
+            function,
+            line,
+            file: script
+                .and_then(Path::file_name)
+                .map(|name| name.to_string_lossy().to_string()),
+        }
+    }
+}
+
+impl fmt::Display for CallFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(
+                f,
+                "in {} called at {} line {}",
+                self.function, file, self.line
+            ),
+            None => write!(f, "in {} called at line {}", self.function, self.line),
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use std::rc::Rc;
+
+use super::{call_frame::CallFrame, lox_function::LoxFunction};
+use crate::{
+    scanner::token::Token,
+    utils::{
+        literal_value::LiteralValue,
+        lox_error::{ErrorKind, LoxError},
+    },
+};
+
+/// Signal a statement raises to leave the normal flow of execution. It
+/// travels up as the `Err` side of the statement visitor's result until
+/// the construct that handles it, so nothing unwinds the Rust stack.
+#[derive(Debug, Clone)]
+pub enum ControlFlow {
+    Break,
+    Continue,
+    //Leaves the innermost function call with the value
+    Return(LiteralValue),
+    //Leaves the innermost function call to have it replaced by a call to
+    //`function` with the arguments bound to its parameters, which runs in
+    //the same frame. `line` is where the call was made
+    TailCall {
+        function: Rc<LoxFunction>,
+        bound: Vec<Option<LiteralValue>>,
+        line: usize,
+    },
+    //Boxed, it is by far the largest and every result carries its size
+    Throw(Box<Thrown>),
+}
+
+/// A value thrown and not caught yet.
+#[derive(Debug, Clone)]
+pub struct Thrown {
+    //Any value can be thrown
+    pub value: LiteralValue,
+    //Where it was raised
+    pub token: Token,
+    //Filled with the call stack when it first leaves a call
+    pub trace: Vec<CallFrame>,
+}
+
+impl ControlFlow {
+    //A builtin runtime error raised at `token`
+    pub fn error(kind: ErrorKind, token: &Token, message: String) -> Self {
+        let error = LoxError::new(kind.name(), message, Some(token.clone()));
+        ControlFlow::throw(LiteralValue::Error(Rc::new(error)), token)
+    }
+
+    //Throws `value` from `token`
+    pub fn throw(value: LiteralValue, token: &Token) -> Self {
+        ControlFlow::Throw(Box::new(Thrown {
+            value,
+            token: token.clone(),
+            trace: Vec::new(),
+        }))
+    }
+}
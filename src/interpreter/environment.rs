@@ -1,27 +1,79 @@
-use std::collections::HashMap;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-use crate::{lox_runtime_error, scanner::token::Token, utils::literal_value::LiteralValue};
+use crate::{
+    scanner::token::Token,
+    utils::{literal_value::LiteralValue, lox_error::ErrorKind},
+};
+
+use super::control_flow::ControlFlow;
 
 pub struct Environment {
     values: HashMap<String, LiteralValue>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Environment {
     pub fn new() -> Self {
         Environment {
             values: HashMap::new(),
+            enclosing: None,
+        }
+    }
+
+    //Scope nested inside `enclosing`, lookups fall through to it
+    pub fn new_enclosed(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Environment {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
         }
     }
 
-    pub fn get(&mut self, name: Token) -> LiteralValue {
-        println!("Values: {:?}", self.values);
+    pub fn get(&self, name: &Token) -> Result<LiteralValue, ControlFlow> {
         match self.values.get(&name.lexame) {
-            Some(value) => return value.clone(),
-            None => lox_runtime_error(name.clone(), format!("Undefined variable {}", &name.lexame)),
+            Some(value) => return Ok(value.clone()),
+            None => match &self.enclosing {
+                Some(enclosing) => enclosing.borrow().get(name),
+                None => Err(undefined(name)),
+            },
         }
     }
 
+    //Only this scope's own variables, not the enclosing ones
+    pub fn bindings(&self) -> impl Iterator<Item = (&String, &LiteralValue)> {
+        self.values.iter()
+    }
+
     pub fn define(&mut self, name: String, value: LiteralValue) {
         self.values.insert(name, value);
     }
+
+    //Updates the innermost scope declaring the variable
+    pub fn assign(
+        &mut self,
+        name: &Token,
+        value: LiteralValue,
+    ) -> Result<LiteralValue, ControlFlow> {
+        if let Some(slot) = self.values.get_mut(&name.lexame) {
+            *slot = value.clone();
+            return Ok(value);
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+            None => Err(undefined(name)),
+        }
+    }
+}
+
+fn undefined(name: &Token) -> ControlFlow {
+    ControlFlow::error(
+        ErrorKind::Name,
+        name,
+        format!("Undefined variable {}", &name.lexame),
+    )
 }
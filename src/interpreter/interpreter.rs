@@ -1,211 +1,832 @@
+use std::{cell::RefCell, path::Path, rc::Rc};
+
+use crate::natives;
 use crate::parser::statement::Statement;
 use crate::{
-    lox_runtime_error,
     parser::{
-        expression::Expression,
+        expression::{Expression, Parameter},
         visitor::{VisitorExpression, VisitorStatement},
     },
-    scanner::token_type::TokenType,
-    utils::literal_value::LiteralValue,
+    scanner::{token::Token, token_type::TokenType},
+    utils::{
+        literal_value::LiteralValue,
+        lox_error::ErrorKind,
+        lox_map::{LoxMap, MapKey},
+        namespace::Namespace,
+        native_function::{NativeContext, NativeFunction},
+        random::Random,
+    },
 };
 
-use super::environment::{self, Environment};
+use super::{
+    call_frame::CallFrame,
+    control_flow::{ControlFlow, Thrown},
+    environment::Environment,
+    iterator::LoxIterator,
+    lox_function::{self, LoxFunction},
+    modules::{self, ModuleLoader},
+    operations,
+    runtime_error::RuntimeError,
+};
 
 pub struct Interpreter {
-    environment: Environment,
+    environment: Rc<RefCell<Environment>>,
+    //When false, dividing by zero follows IEEE 754 and yields inf or NaN
+    checked_division: bool,
+    natives: NativeContext,
+    modules: ModuleLoader,
+    //File of the code being run, imports resolve relative to it
+    script: Option<Rc<Path>>,
+    //Calls in progress, innermost last
+    frames: Vec<CallFrame>,
+    //How many calls can be in progress at once before a stack overflow
+    max_depth: usize,
 }
 
-impl VisitorStatement<LiteralValue> for Interpreter {
-    fn visit(&mut self, expression: &crate::parser::statement::Statement) -> LiteralValue {
-        match expression {
-            Statement::Expr { expression } => self.evaluate(Box::new(expression.clone())),
-            Statement::Print { expression } => match self.evaluate(Box::new(expression.clone())) {
-                value => {
-                    println!("{}", value);
-                    LiteralValue::None
-                }
-            },
-            Statement::Var { name, initializer } => match initializer {
-                Expression::Literal { value } => match value {
-                    LiteralValue::None => {
-                        self.environment
-                            .define(name.clone().lexame, LiteralValue::None);
-                        LiteralValue::None
-                    }
-                    _ => {
-                        self.evaluate(Box::new(initializer.clone()));
-                        self.environment.define(name.clone().lexame, value.clone());
-                        LiteralValue::None
-                    }
-                },
-                _ => LiteralValue::None,
-            },
+//Arguments passed by name, in the order they were written, with the name
+//tokens of the call
+type NamedArguments<'a> = Vec<(&'a Token, LiteralValue)>;
+
+/// How many calls can be nested unless `set_max_depth` says otherwise.
+/// Every nested Lox call also nests on the Rust stack, which has to be
+/// big enough to hold them.
+pub const DEFAULT_MAX_DEPTH: usize = 1000;
+
+impl VisitorStatement<Result<(), ControlFlow>> for Interpreter {
+    fn visit_expr(&mut self, expression: &Expression) -> Result<(), ControlFlow> {
+        self.evaluate(expression)?;
+        Ok(())
+    }
+
+    fn visit_print(&mut self, expression: &Expression) -> Result<(), ControlFlow> {
+        let value = self.evaluate(expression)?;
+        println!("{}", value);
+        Ok(())
+    }
+
+    fn visit_var(&mut self, name: &Token, initializer: &Expression) -> Result<(), ControlFlow> {
+        let value = self.evaluate(initializer)?;
+        self.environment
+            .borrow_mut()
+            .define(name.lexame.clone(), value);
+        Ok(())
+    }
+
+    fn visit_block(&mut self, statements: &[Statement]) -> Result<(), ControlFlow> {
+        let scope = Environment::new_enclosed(self.environment.clone());
+        self.execute_block(statements, scope)
+    }
+
+    fn visit_if(
+        &mut self,
+        condition: &Expression,
+        then_branch: &Statement,
+        else_branch: Option<&Statement>,
+    ) -> Result<(), ControlFlow> {
+        let condition_evaluated = self.evaluate(condition)?;
+        match (operations::is_truthy(&condition_evaluated), else_branch) {
+            (true, _) => self.execute(then_branch),
+            (false, Some(else_branch)) => self.execute(else_branch),
+            (false, None) => Ok(()),
         }
     }
-}
-impl VisitorExpression<LiteralValue> for Interpreter {
-    fn visit(&mut self, expression: &Expression) -> LiteralValue {
-        match expression {
-            Expression::Unary { operator, right } => {
-                println!("UNARY");
-                let right_evaluated: LiteralValue = self.evaluate(right.clone());
-
-                match operator.token_type {
-                    TokenType::Bang => LiteralValue::Boolean(!self.is_truthy(right_evaluated)),
-                    TokenType::Minus => match right_evaluated {
-                        LiteralValue::Float(value) => LiteralValue::Float(-value),
-                        LiteralValue::Boolean(_) => lox_runtime_error(
-                            operator.clone(),
-                            "Tried to negate boolean".to_string(),
-                        ),
-                        LiteralValue::String(_) => lox_runtime_error(
-                            operator.clone(),
-                            "Tried to negate string".to_string(),
-                        ),
-                        LiteralValue::Nil => {
-                            lox_runtime_error(operator.clone(), "Tried to negate nil".to_string())
-                        }
-                        LiteralValue::None => lox_runtime_error(
-                            operator.clone(),
-                            "None value cannot be used, that is really strange".to_string(),
-                        ),
-                    },
-                    _ => LiteralValue::Nil,
-                }
+
+    fn visit_while(
+        &mut self,
+        condition: &Expression,
+        body: &Statement,
+        increment: Option<&Expression>,
+    ) -> Result<(), ControlFlow> {
+        loop {
+            let condition_evaluated = self.evaluate(condition)?;
+            if !operations::is_truthy(&condition_evaluated) {
+                break;
             }
-            Expression::Literal { value } => value.clone(),
-            Expression::Binary {
-                left,
-                operator,
-                right,
-            } => {
-                let left_evaluated = self.evaluate(left.clone());
-                let right_evaluated = self.evaluate(right.clone());
-                match operator.token_type {
-                    //ARITMETICHS
-                    TokenType::Minus => {
-                        self.evaluate_arithmetic_op(left_evaluated, right_evaluated, |lv, rv| {
-                            lv - rv
-                        })
-                    }
-                    TokenType::Star => {
-                        self.evaluate_arithmetic_op(left_evaluated, right_evaluated, |lv, rv| {
-                            lv * rv
-                        })
-                    }
-                    TokenType::Slash => {
-                        self.evaluate_arithmetic_op(left_evaluated, right_evaluated, |lv, rv| {
-                            lv / rv
-                        })
-                    }
-                    TokenType::Plus => match (left_evaluated, right_evaluated) {
-                        (LiteralValue::String(lv), LiteralValue::String(rv)) => {
-                            LiteralValue::String(format!("{}{}", lv, rv))
-                        }
-                        (LiteralValue::Float(lv), LiteralValue::Float(rv)) => {
-                            LiteralValue::Float(lv + rv)
-                        }
-                        _ => lox_runtime_error(
-                            operator.clone(),
-                            "Addition can be done between string and numbers".to_string(),
-                        ),
-                    },
+            match self.execute(body) {
+                Err(ControlFlow::Break) => break,
+                Ok(()) | Err(ControlFlow::Continue) => {}
+                Err(signal) => return Err(signal),
+            }
+            if let Some(increment) = increment {
+                self.evaluate(increment)?;
+            }
+        }
+        Ok(())
+    }
 
-                    //BOOLEAN OPERATORS
-                    TokenType::LessEqual => {
-                        self.evaluate_boolean_op(left_evaluated, right_evaluated, |lv, rv| lv <= rv)
-                    }
-                    TokenType::Less => {
-                        self.evaluate_boolean_op(left_evaluated, right_evaluated, |lv, rv| lv < rv)
-                    }
-                    TokenType::Greater => {
-                        self.evaluate_boolean_op(left_evaluated, right_evaluated, |lv, rv| lv > rv)
-                    }
-                    TokenType::GreaterEqual => {
-                        self.evaluate_boolean_op(left_evaluated, right_evaluated, |lv, rv| lv >= rv)
-                    }
-                    TokenType::BangEqual => {
-                        LiteralValue::Boolean(!self.is_equal(left_evaluated, right_evaluated))
-                    }
-                    TokenType::EqualEqual => {
-                        LiteralValue::Boolean(self.is_equal(left_evaluated, right_evaluated))
-                    }
-                    _ => todo!(),
+    fn visit_for_in(
+        &mut self,
+        name: &Token,
+        iterable: &Expression,
+        body: &Statement,
+    ) -> Result<(), ControlFlow> {
+        let iterable_evaluated = self.evaluate(iterable)?;
+        let iterator = match LoxIterator::new(&iterable_evaluated) {
+            Ok(iterator) => iterator,
+            Err(message) => return Err(ControlFlow::error(ErrorKind::Type, name, message)),
+        };
+        for element in iterator {
+            //Fresh scope per iteration, the loop variable is not shared between them
+            let mut scope = Environment::new_enclosed(self.environment.clone());
+            scope.define(name.lexame.clone(), element);
+            match self.execute_block(std::slice::from_ref(body), scope) {
+                Err(ControlFlow::Break) => break,
+                Ok(()) | Err(ControlFlow::Continue) => {}
+                Err(signal) => return Err(signal),
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_break(&mut self, _keyword: &Token) -> Result<(), ControlFlow> {
+        Err(ControlFlow::Break)
+    }
+
+    fn visit_continue(&mut self, _keyword: &Token) -> Result<(), ControlFlow> {
+        Err(ControlFlow::Continue)
+    }
+
+    fn visit_return(
+        &mut self,
+        _keyword: &Token,
+        value: Option<&Expression>,
+        tail: bool,
+    ) -> Result<(), ControlFlow> {
+        let value = match value {
+            Some(value) if tail => self.evaluate_tail(value)?,
+            Some(value) => self.evaluate(value)?,
+            None => LiteralValue::Nil,
+        };
+        Err(ControlFlow::Return(value))
+    }
+
+    fn visit_throw(&mut self, keyword: &Token, value: &Expression) -> Result<(), ControlFlow> {
+        let value = match self.evaluate(value)? {
+            //error() values learn where they were thrown
+            LiteralValue::Error(error) if error.token.is_none() => {
+                let mut error = error.as_ref().clone();
+                error.token = Some(keyword.clone());
+                LiteralValue::Error(Rc::new(error))
+            }
+            value => value,
+        };
+        Err(ControlFlow::throw(value, keyword))
+    }
+
+    fn visit_try(
+        &mut self,
+        body: &[Statement],
+        catch: Option<(&Token, &[Statement])>,
+        finally: Option<&[Statement]>,
+    ) -> Result<(), ControlFlow> {
+        let scope = Environment::new_enclosed(self.environment.clone());
+        let mut result = self.execute_block(body, scope);
+        if let (Err(ControlFlow::Throw(thrown)), Some((name, handler))) = (&result, catch) {
+            let mut scope = Environment::new_enclosed(self.environment.clone());
+            scope.define(name.lexame.clone(), thrown.value.clone());
+            result = self.execute_block(handler, scope);
+        }
+        //Runs however the body left, and its own break or throw wins
+        if let Some(finally) = finally {
+            let scope = Environment::new_enclosed(self.environment.clone());
+            let finally_result = self.execute_block(finally, scope);
+            if finally_result.is_err() {
+                result = finally_result;
+            }
+        }
+        result
+    }
+
+    fn visit_import(
+        &mut self,
+        keyword: &Token,
+        path: &Token,
+        alias: &Token,
+    ) -> Result<(), ControlFlow> {
+        let namespace = self.import(keyword, path)?;
+        self.environment
+            .borrow_mut()
+            .define(alias.lexame.clone(), LiteralValue::Namespace(namespace));
+        Ok(())
+    }
+
+    fn visit_from_import(
+        &mut self,
+        keyword: &Token,
+        path: &Token,
+        names: &[Token],
+    ) -> Result<(), ControlFlow> {
+        let namespace = self.import(keyword, path)?;
+        for name in names {
+            match namespace.get(&name.lexame) {
+                Some(value) => self
+                    .environment
+                    .borrow_mut()
+                    .define(name.lexame.clone(), value),
+                None => {
+                    return Err(ControlFlow::error(
+                        ErrorKind::Import,
+                        name,
+                        format!(
+                            "Module '{}' has no export '{}'",
+                            namespace.name, name.lexame
+                        ),
+                    ))
                 }
             }
-            Expression::Grouping { expression } => self.evaluate(Box::new(Expression::Grouping {
-                expression: expression.clone(),
-            })),
-            Expression::Variable { name } => self.environment.get(name.clone()),
-            Expression::Assign { name, value } => {
-                let value_evaluated = self.evaluate(value.clone());
-                self.environment
-                    .define(name.clone().lexame, value_evaluated.clone());
-                return value_evaluated.clone();
+        }
+        Ok(())
+    }
+}
+
+impl VisitorExpression<Result<LiteralValue, ControlFlow>> for Interpreter {
+    fn visit_unary(
+        &mut self,
+        operator: &Token,
+        right: &Expression,
+    ) -> Result<LiteralValue, ControlFlow> {
+        let right_evaluated = self.evaluate(right)?;
+        operations::unary(operator.token_type, right_evaluated)
+            .map_err(|failure| fail(operator, failure))
+    }
+
+    fn visit_literal(&mut self, value: &LiteralValue) -> Result<LiteralValue, ControlFlow> {
+        Ok(value.clone())
+    }
+
+    fn visit_binary(
+        &mut self,
+        left: &Expression,
+        operator: &Token,
+        right: &Expression,
+    ) -> Result<LiteralValue, ControlFlow> {
+        let left_evaluated = self.evaluate(left)?;
+        let right_evaluated = self.evaluate(right)?;
+        operations::binary(
+            operator.token_type,
+            operator,
+            left_evaluated,
+            right_evaluated,
+            self.checked_division,
+        )
+        .map_err(|failure| fail(operator, failure))
+    }
+
+    fn visit_grouping(&mut self, expression: &Expression) -> Result<LiteralValue, ControlFlow> {
+        self.evaluate(expression)
+    }
+
+    fn visit_variable(&mut self, name: &Token) -> Result<LiteralValue, ControlFlow> {
+        self.environment.borrow().get(name)
+    }
+
+    fn visit_assign(
+        &mut self,
+        name: &Token,
+        value: &Expression,
+    ) -> Result<LiteralValue, ControlFlow> {
+        let value_evaluated = self.evaluate(value)?;
+        return self.environment.borrow_mut().assign(name, value_evaluated);
+    }
+
+    fn visit_logical(
+        &mut self,
+        left: &Expression,
+        operator: &Token,
+        right: &Expression,
+    ) -> Result<LiteralValue, ControlFlow> {
+        let left_evaluated = self.evaluate(left)?;
+        let left_truthy = operations::is_truthy(&left_evaluated);
+        match (operator.token_type, left_truthy) {
+            (TokenType::Or, true) | (TokenType::And, false) => Ok(left_evaluated),
+            _ => self.evaluate(right),
+        }
+    }
+
+    fn visit_conditional(
+        &mut self,
+        condition: &Expression,
+        then_branch: &Expression,
+        else_branch: &Expression,
+    ) -> Result<LiteralValue, ControlFlow> {
+        let condition_evaluated = self.evaluate(condition)?;
+        match operations::is_truthy(&condition_evaluated) {
+            true => self.evaluate(then_branch),
+            false => self.evaluate(else_branch),
+        }
+    }
+
+    fn visit_sequence(&mut self, expressions: &[Expression]) -> Result<LiteralValue, ControlFlow> {
+        let mut value = LiteralValue::Nil;
+        for expression in expressions {
+            value = self.evaluate(expression)?;
+        }
+        Ok(value)
+    }
+
+    fn visit_list(&mut self, elements: &[Expression]) -> Result<LiteralValue, ControlFlow> {
+        let mut values = Vec::new();
+        for element in elements {
+            values.push(self.evaluate(element)?);
+        }
+        Ok(LiteralValue::list(values))
+    }
+
+    fn visit_map(
+        &mut self,
+        brace: &Token,
+        entries: &[(Expression, Expression)],
+    ) -> Result<LiteralValue, ControlFlow> {
+        let mut map = LoxMap::new();
+        for (key, value) in entries {
+            let key_evaluated = self.evaluate(key)?;
+            let value_evaluated = self.evaluate(value)?;
+            match MapKey::from_value(&key_evaluated) {
+                Ok(key) => map.insert(key, value_evaluated),
+                Err(message) => return Err(ControlFlow::error(ErrorKind::Type, brace, message)),
             }
         }
+        Ok(LiteralValue::map(map))
+    }
+
+    fn visit_index(
+        &mut self,
+        object: &Expression,
+        bracket: &Token,
+        index: &Expression,
+    ) -> Result<LiteralValue, ControlFlow> {
+        let object_evaluated = self.evaluate(object)?;
+        let index_evaluated = self.evaluate(index)?;
+        operations::index(object_evaluated, &index_evaluated)
+            .map_err(|failure| fail(bracket, failure))
+    }
+
+    fn visit_set_index(
+        &mut self,
+        object: &Expression,
+        bracket: &Token,
+        index: &Expression,
+        value: &Expression,
+    ) -> Result<LiteralValue, ControlFlow> {
+        let object_evaluated = self.evaluate(object)?;
+        let index_evaluated = self.evaluate(index)?;
+        let value_evaluated = self.evaluate(value)?;
+        operations::set_index(object_evaluated, &index_evaluated, value_evaluated)
+            .map_err(|failure| fail(bracket, failure))
+    }
+
+    fn visit_get(
+        &mut self,
+        object: &Expression,
+        name: &Token,
+    ) -> Result<LiteralValue, ControlFlow> {
+        let object_evaluated = self.evaluate(object)?;
+        operations::get_member(&object_evaluated, &name.lexame)
+            .map_err(|failure| fail(name, failure))
+    }
+
+    fn visit_lambda(
+        &mut self,
+        _keyword: &Token,
+        name: Option<&Token>,
+        params: &Rc<Vec<Parameter>>,
+        body: &Rc<Vec<Statement>>,
+    ) -> Result<LiteralValue, ControlFlow> {
+        Ok(LiteralValue::Function(Rc::new(LoxFunction {
+            name: name.map(|name| name.lexame.clone()),
+            params: params.clone(),
+            body: body.clone(),
+            closure: self.environment.clone(),
+            script: self.script.clone(),
+        })))
+    }
+
+    fn visit_interpolation(&mut self, parts: &[Expression]) -> Result<LiteralValue, ControlFlow> {
+        let mut text = String::new();
+        for part in parts {
+            //Same formatting as print
+            text.push_str(&self.evaluate(part)?.to_string());
+        }
+        Ok(LiteralValue::String(text))
+    }
+
+    fn visit_call(
+        &mut self,
+        callee: &Expression,
+        paren: &Token,
+        arguments: &[Expression],
+        named: &[(Token, Expression)],
+    ) -> Result<LiteralValue, ControlFlow> {
+        let (callee, arguments, named) = self.evaluate_call(callee, arguments, named)?;
+        self.call(callee, arguments, named, paren)
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Interpreter {
     pub fn new() -> Self {
         Interpreter {
-            environment: Environment::new(),
+            environment: Rc::new(RefCell::new(Self::builtins())),
+            checked_division: true,
+            natives: NativeContext::new(),
+            modules: ModuleLoader::new(),
+            script: None,
+            frames: Vec::new(),
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+
+    //A fresh global scope holding every native
+    fn builtins() -> Environment {
+        let mut environment = Environment::new();
+        for native in natives::globals() {
+            environment.define(native.name.to_string(), LiteralValue::Native(native));
+        }
+        for namespace in natives::namespaces() {
+            environment.define(
+                namespace.name.clone(),
+                LiteralValue::Namespace(Rc::new(namespace)),
+            );
         }
+        environment
+    }
+
+    /// Whether `/`, `%` and `~/` by zero are runtime errors (the default)
+    /// or silently produce inf/NaN.
+    pub fn set_checked_division(&mut self, checked: bool) {
+        self.checked_division = checked;
+    }
+
+    /// Fixes the seed behind `random`, `random_int`, `choice` and `shuffle`
+    /// so runs are reproducible. Unseeded interpreters use the clock.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.natives.random = Random::new(seed);
+    }
+
+    /// Lets scripts read and write files with `read_file`, `write_file`,
+    /// `append_file`, `list_dir` and `exists`. Off by default so embedded
+    /// scripts are sandboxed, the command line turns it on.
+    pub fn set_filesystem(&mut self, allowed: bool) {
+        self.natives.filesystem = allowed;
+    }
+
+    /// The file the statements come from, so its imports resolve relative
+    /// to it. Without one they resolve against the working directory.
+    pub fn set_script(&mut self, path: &Path) {
+        self.script = Some(Rc::from(path));
+    }
+
+    /// How many calls can be nested before the program stops with a
+    /// "Stack overflow" error. Tail calls don't count, they reuse the frame
+    /// of the function making them.
+    pub fn set_max_depth(&mut self, depth: usize) {
+        self.max_depth = depth;
     }
 
-    pub fn interpet(&mut self, statements: Vec<Statement>) {
+    /// Runs the statements in order. An uncaught throw stops the program
+    /// and comes back with the calls it went through.
+    pub fn interpet(&mut self, statements: Vec<Statement>) -> Result<(), Box<RuntimeError>> {
         for statement in statements {
-            self.execute(statement);
+            //The parser keeps break and continue inside loops, only throws escape here
+            if let Err(ControlFlow::Throw(thrown)) = self.execute(&statement) {
+                let Thrown {
+                    value,
+                    token,
+                    trace,
+                } = *thrown;
+                let token = match &value {
+                    LiteralValue::Error(error) => error.token.clone().unwrap_or(token),
+                    _ => token,
+                };
+                return Err(Box::new(RuntimeError {
+                    value,
+                    token,
+                    trace,
+                }));
+            }
         }
+        Ok(())
     }
 
-    fn execute(&mut self, statement: Statement) -> LiteralValue {
+    //Runs `call` with a frame for `function` on the stack. A throw leaving it
+    //keeps a snapshot of the stack as it was where the throw happened
+    fn in_frame<T>(
+        &mut self,
+        function: String,
+        call_site: &Token,
+        call: impl FnOnce(&mut Self) -> Result<T, ControlFlow>,
+    ) -> Result<T, ControlFlow> {
+        if self.frames.len() >= self.max_depth {
+            return Err(ControlFlow::error(
+                ErrorKind::Recursion,
+                call_site,
+                format!(
+                    "Stack overflow, calls nested more than {} deep",
+                    self.max_depth
+                ),
+            ));
+        }
+        let frame = CallFrame::new(function, call_site.line, self.script.as_deref());
+        self.frames.push(frame);
+        let mut result = call(self);
+        if let Err(ControlFlow::Throw(thrown)) = &mut result {
+            if thrown.trace.is_empty() {
+                thrown.trace = self.frames.iter().rev().cloned().collect();
+            }
+        }
+        self.frames.pop();
+        result
+    }
+
+    fn execute(&mut self, statement: &Statement) -> Result<(), ControlFlow> {
         return statement.accept(self);
     }
 
-    fn evaluate(&mut self, expression: Box<Expression>) -> LiteralValue {
+    //The enclosing scope is restored even when a signal cuts the block short
+    fn execute_block(
+        &mut self,
+        statements: &[Statement],
+        scope: Environment,
+    ) -> Result<(), ControlFlow> {
+        let previous = std::mem::replace(&mut self.environment, Rc::new(RefCell::new(scope)));
+        let mut result = Ok(());
+        for statement in statements {
+            result = self.execute(statement);
+            if result.is_err() {
+                break;
+            }
+        }
+        self.environment = previous;
+        result
+    }
+
+    /// Runs the module named by `path` once and returns its exports. Errors
+    /// finding it and errors it throws while running surface at the import.
+    fn import(&mut self, keyword: &Token, path: &Token) -> Result<Rc<Namespace>, ControlFlow> {
+        if !self.natives.filesystem {
+            return Err(ControlFlow::error(
+                ErrorKind::Import,
+                keyword,
+                "import needs filesystem access, which this interpreter does not allow".to_string(),
+            ));
+        }
+        let requested = match &path.literal {
+            LiteralValue::String(requested) => requested,
+            _ => unreachable!("the parser only accepts a string as module path"),
+        };
+        let import_error = |message| ControlFlow::error(ErrorKind::Import, path, message);
+        let resolved = self
+            .modules
+            .resolve(requested, self.script.as_deref())
+            .map_err(import_error)?;
+        if let Some(namespace) = self.modules.cached(&resolved) {
+            return Ok(namespace);
+        }
+        self.modules.enter(&resolved).map_err(import_error)?;
+        let statements = match modules::load(&resolved) {
+            Ok(statements) => statements,
+            Err(message) => {
+                self.modules.exit(&resolved, None);
+                return Err(import_error(message));
+            }
+        };
+
+        //Its own globals over a fresh copy of the natives, so nothing leaks in
+        //or out except through the namespace
+        let builtins = Rc::new(RefCell::new(Self::builtins()));
+        let globals = Rc::new(RefCell::new(Environment::new_enclosed(builtins)));
+        let previous_environment = std::mem::replace(&mut self.environment, globals.clone());
+        let module = format!("<module {}>", modules::module_name(&resolved));
+        let result = self.in_frame(module, path, |interpreter| {
+            let previous_script = interpreter.script.replace(Rc::from(resolved.as_path()));
+            let mut result = Ok(());
+            for statement in &statements {
+                result = interpreter.execute(statement);
+                if result.is_err() {
+                    break;
+                }
+            }
+            interpreter.script = previous_script;
+            result
+        });
+        self.environment = previous_environment;
+        if let Err(signal) = result {
+            self.modules.exit(&resolved, None);
+            return Err(signal);
+        }
+
+        let mut namespace = Namespace::new(&modules::module_name(&resolved));
+        for (name, value) in globals.borrow().bindings() {
+            namespace.define(name, value.clone());
+        }
+        let namespace = Rc::new(namespace);
+        self.modules.exit(&resolved, Some(namespace.clone()));
+        Ok(namespace)
+    }
+
+    fn evaluate(&mut self, expression: &Expression) -> Result<LiteralValue, ControlFlow> {
         return expression.accept(self);
     }
 
-    //TODO Refactor this code using a trait maybe
-    fn evaluate_arithmetic_op(
-        &self,
-        left: LiteralValue,
-        right: LiteralValue,
-        op: fn(f64, f64) -> f64,
-    ) -> LiteralValue {
-        match (left, right) {
-            (LiteralValue::Float(lv), LiteralValue::Float(rv)) => LiteralValue::Float(op(lv, rv)),
-            _ => todo!(),
+    //Evaluates a returned expression, a Lox function it ends up calling is
+    //left to the call running this one, which reuses its frame for it
+    fn evaluate_tail(&mut self, expression: &Expression) -> Result<LiteralValue, ControlFlow> {
+        match expression {
+            Expression::Call {
+                callee,
+                paren,
+                arguments,
+                named,
+            } => {
+                let (callee, arguments, named) = self.evaluate_call(callee, arguments, named)?;
+                match callee {
+                    LiteralValue::Function(function) => Err(ControlFlow::TailCall {
+                        bound: self.bind_arguments(&function, arguments, named, paren)?,
+                        function,
+                        line: paren.line,
+                    }),
+                    callee => self.call(callee, arguments, named, paren),
+                }
+            }
+            Expression::Grouping { expression } => self.evaluate_tail(expression),
+            Expression::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let condition_evaluated = self.evaluate(condition)?;
+                match operations::is_truthy(&condition_evaluated) {
+                    true => self.evaluate_tail(then_branch),
+                    false => self.evaluate_tail(else_branch),
+                }
+            }
+            other => self.evaluate(other),
         }
     }
-    fn evaluate_boolean_op(
-        &self,
-        left: LiteralValue,
-        right: LiteralValue,
-        op: fn(f64, f64) -> bool,
-    ) -> LiteralValue {
-        match (left, right) {
-            (LiteralValue::Float(lv), LiteralValue::Float(rv)) => LiteralValue::Boolean(op(lv, rv)),
-            _ => todo!(),
+
+    //The callee and the arguments of a call, evaluated left to right
+    fn evaluate_call<'a>(
+        &mut self,
+        callee: &Expression,
+        arguments: &[Expression],
+        named: &'a [(Token, Expression)],
+    ) -> Result<(LiteralValue, Vec<LiteralValue>, NamedArguments<'a>), ControlFlow> {
+        let callee = self.evaluate(callee)?;
+        let mut arguments_evaluated = Vec::new();
+        for argument in arguments {
+            arguments_evaluated.push(self.evaluate(argument)?);
         }
+        let mut named_evaluated = Vec::new();
+        for (name, argument) in named {
+            named_evaluated.push((name, self.evaluate(argument)?));
+        }
+        Ok((callee, arguments_evaluated, named_evaluated))
     }
-    fn is_truthy(&self, value: LiteralValue) -> bool {
-        match value {
-            LiteralValue::Boolean(value) => value,
-            LiteralValue::None => false,
-            _ => true,
+
+    fn call(
+        &mut self,
+        callee: LiteralValue,
+        mut arguments: Vec<LiteralValue>,
+        named: NamedArguments<'_>,
+        paren: &Token,
+    ) -> Result<LiteralValue, ControlFlow> {
+        if let Some((name, _)) = named.first() {
+            if !matches!(callee, LiteralValue::Function(_)) {
+                return Err(ControlFlow::error(
+                    ErrorKind::Type,
+                    name,
+                    format!("Builtins don't take named arguments, got '{}'", name.lexame),
+                ));
+            }
+        }
+        match callee {
+            LiteralValue::Native(native) => self.call_native(&native, arguments, 0, paren),
+            LiteralValue::BoundMethod { receiver, method } => {
+                arguments.insert(0, *receiver);
+                self.call_native(&method, arguments, 1, paren)
+            }
+            LiteralValue::Function(function) => {
+                self.call_function(function, arguments, named, paren)
+            }
+            other => Err(ControlFlow::error(
+                ErrorKind::Type,
+                paren,
+                format!("Can only call functions, got {}", other.type_name()),
+            )),
         }
     }
 
-    fn is_equal(&self, lv: LiteralValue, rv: LiteralValue) -> bool {
-        if lv == LiteralValue::None && rv == LiteralValue::None {
-            return true;
+    fn call_native(
+        &mut self,
+        native: &NativeFunction,
+        arguments: Vec<LiteralValue>,
+        receivers: usize,
+        paren: &Token,
+    ) -> Result<LiteralValue, ControlFlow> {
+        if let Err(message) = native.check_arity(arguments.len(), receivers) {
+            return Err(ControlFlow::error(ErrorKind::Type, paren, message));
         }
-        if lv == LiteralValue::None {
-            return false;
+        self.in_frame(format!("{}()", native.name), paren, |interpreter| {
+            (native.function)(&mut interpreter.natives, arguments)
+                .map_err(|message| ControlFlow::error(ErrorKind::Generic, paren, message))
+        })
+    }
+
+    //Tail calls the function makes run here one after another, in the
+    //frame pushed for it, instead of nesting deeper
+    fn call_function(
+        &mut self,
+        function: Rc<LoxFunction>,
+        arguments: Vec<LiteralValue>,
+        named: NamedArguments<'_>,
+        paren: &Token,
+    ) -> Result<LiteralValue, ControlFlow> {
+        let bound = self.bind_arguments(&function, arguments, named, paren)?;
+        self.in_frame(function.display_name(), paren, |interpreter| {
+            let (mut function, mut bound) = (function, bound);
+            loop {
+                let scope = Environment::new_enclosed(function.closure.clone());
+                let previous =
+                    std::mem::replace(&mut interpreter.environment, Rc::new(RefCell::new(scope)));
+                let previous_script =
+                    std::mem::replace(&mut interpreter.script, function.script.clone());
+                let result = interpreter.run_function(&function, bound);
+                interpreter.environment = previous;
+                interpreter.script = previous_script;
+                match result {
+                    Err(ControlFlow::TailCall {
+                        function: callee,
+                        bound: arguments,
+                        line,
+                    }) => {
+                        //The tail call was made in the body of `function`
+                        let frame =
+                            CallFrame::new(callee.display_name(), line, function.script.as_deref());
+                        if let Some(top) = interpreter.frames.last_mut() {
+                            *top = frame;
+                        }
+                        (function, bound) = (callee, arguments);
+                    }
+                    result => return result,
+                }
+            }
+        })
+    }
+
+    //Named arguments at fault are blamed at their name, anything else at the call
+    fn bind_arguments(
+        &self,
+        function: &LoxFunction,
+        arguments: Vec<LiteralValue>,
+        named: NamedArguments<'_>,
+        paren: &Token,
+    ) -> Result<Vec<Option<LiteralValue>>, ControlFlow> {
+        let names: Vec<&Token> = named.iter().map(|(name, _)| *name).collect();
+        let named = named
+            .into_iter()
+            .map(|(name, value)| (name.lexame.as_str(), value))
+            .collect();
+        lox_function::bind_arguments(&function.display_name(), &function.params, arguments, named)
+            .map_err(|error| {
+                let token = error.named.map_or(paren, |position| names[position]);
+                ControlFlow::error(ErrorKind::Type, token, error.message)
+            })
+    }
+
+    //Runs a call in the function's fresh scope, the current environment
+    fn run_function(
+        &mut self,
+        function: &LoxFunction,
+        bound: Vec<Option<LiteralValue>>,
+    ) -> Result<LiteralValue, ControlFlow> {
+        for (param, value) in function.params.iter().zip(bound) {
+            let value = match (value, &param.default) {
+                (Some(value), _) => value,
+                //Evaluated on every call that needs it, seeing the parameters before it
+                (None, Some(default)) => self.evaluate(default)?,
+                (None, None) => unreachable!("bind_arguments gives every other parameter a value"),
+            };
+            self.environment
+                .borrow_mut()
+                .define(param.name.lexame.clone(), value);
+        }
+        for statement in function.body.iter() {
+            match self.execute(statement) {
+                Ok(()) => {}
+                Err(ControlFlow::Return(value)) => return Ok(value),
+                Err(signal) => return Err(signal),
+            }
         }
-        return lv == rv;
+        Ok(LiteralValue::Nil)
     }
 }
+
+//Raises a failure of the shared operations at `token`
+fn fail(token: &Token, (kind, message): operations::Failure) -> ControlFlow {
+    ControlFlow::error(kind, token, message)
+}
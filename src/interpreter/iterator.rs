@@ -0,0 +1,74 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::utils::{literal_value::LiteralValue, lox_range::LoxRange};
+
+/// Cursor used by `for (x in iterable)`.
+///
+/// Lists are read live, so elements pushed during the loop are visited.
+/// Maps yield their keys and strings their characters, both as they were
+/// when the loop started.
+#[derive(Debug)]
+pub enum LoxIterator {
+    List {
+        elements: Rc<RefCell<Vec<LiteralValue>>>,
+        position: usize,
+    },
+    Snapshot(std::vec::IntoIter<LiteralValue>),
+    Range {
+        range: LoxRange,
+        position: usize,
+    },
+}
+
+impl LoxIterator {
+    pub fn new(iterable: &LiteralValue) -> Result<LoxIterator, String> {
+        match iterable {
+            LiteralValue::List(elements) => Ok(LoxIterator::List {
+                elements: elements.clone(),
+                position: 0,
+            }),
+            LiteralValue::Map(entries) => {
+                let keys: Vec<LiteralValue> = entries
+                    .borrow()
+                    .iter()
+                    .map(|(key, _)| key.to_value())
+                    .collect();
+                Ok(LoxIterator::Snapshot(keys.into_iter()))
+            }
+            LiteralValue::String(value) => {
+                let characters: Vec<LiteralValue> = value
+                    .chars()
+                    .map(|character| LiteralValue::String(character.to_string()))
+                    .collect();
+                Ok(LoxIterator::Snapshot(characters.into_iter()))
+            }
+            LiteralValue::Range(range) => Ok(LoxIterator::Range {
+                range: *range,
+                position: 0,
+            }),
+            other => Err(format!("Cannot iterate over {}", other.type_name())),
+        }
+    }
+}
+
+impl Iterator for LoxIterator {
+    type Item = LiteralValue;
+
+    fn next(&mut self) -> Option<LiteralValue> {
+        match self {
+            LoxIterator::List { elements, position } => {
+                let element = elements.borrow().get(*position).cloned();
+                *position += 1;
+                element
+            }
+            LoxIterator::Snapshot(values) => values.next(),
+            LoxIterator::Range { range, position } => match *position < range.len() {
+                true => {
+                    *position += 1;
+                    Some(LiteralValue::Float(range.get(*position - 1)))
+                }
+                false => None,
+            },
+        }
+    }
+}
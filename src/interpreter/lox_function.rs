@@ -0,0 +1,136 @@
+use std::{cell::RefCell, fmt, path::Path, rc::Rc};
+
+use crate::{
+    parser::{expression::Parameter, statement::Statement},
+    utils::literal_value::LiteralValue,
+};
+
+use super::environment::Environment;
+
+/// A function written in Lox, with the scope it was created in so it can
+/// keep reading and assigning the variables around it after that scope ends.
+pub struct LoxFunction {
+    //None for lambdas
+    pub name: Option<String>,
+    pub params: Rc<Vec<Parameter>>,
+    pub body: Rc<Vec<Statement>>,
+    pub closure: Rc<RefCell<Environment>>,
+    //File it was written in, imports in its body resolve relative to it
+    pub script: Option<Rc<Path>>,
+}
+
+impl LoxFunction {
+    //How it shows in values and tracebacks
+    pub fn display_name(&self) -> String {
+        match &self.name {
+            Some(name) => format!("{}()", name),
+            None => "<lambda>".to_string(),
+        }
+    }
+}
+
+impl fmt::Debug for LoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LoxFunction({})", self.display_name())
+    }
+}
+
+/// What argument binding needs to know about a parameter, whichever backend
+/// compiled the function.
+pub trait ParameterSpec {
+    fn name(&self) -> &str;
+    fn has_default(&self) -> bool;
+    fn is_rest(&self) -> bool;
+}
+
+impl ParameterSpec for Parameter {
+    fn name(&self) -> &str {
+        &self.name.lexame
+    }
+
+    fn has_default(&self) -> bool {
+        self.default.is_some()
+    }
+
+    fn is_rest(&self) -> bool {
+        self.rest
+    }
+}
+
+/// Why a call's arguments don't fit the parameters. `named` is the position
+/// of the named argument at fault, None when the call as a whole is.
+pub struct ArgumentError {
+    pub named: Option<usize>,
+    pub message: String,
+}
+
+/// Matches arguments to parameters: positional ones in order, the extras
+/// going to the rest parameter, then the named ones. None leaves a
+/// parameter to its default.
+pub fn bind_arguments<P: ParameterSpec>(
+    function: &str,
+    params: &[P],
+    arguments: Vec<LiteralValue>,
+    named: Vec<(&str, LiteralValue)>,
+) -> Result<Vec<Option<LiteralValue>>, ArgumentError> {
+    let mut bound: Vec<Option<LiteralValue>> = vec![None; params.len()];
+    let given = arguments.len();
+    let mut positional = arguments.into_iter();
+    for (slot, param) in bound.iter_mut().zip(params) {
+        *slot = match param.is_rest() {
+            true => Some(LiteralValue::list(positional.by_ref().collect())),
+            false => positional.next(),
+        };
+    }
+    if positional.next().is_some() {
+        return Err(ArgumentError {
+            named: None,
+            message: format!(
+                "{} takes at most {} positional arguments but got {}",
+                function,
+                params.len(),
+                given
+            ),
+        });
+    }
+
+    for (position, (name, value)) in named.into_iter().enumerate() {
+        let message = match params.iter().position(|param| param.name() == name) {
+            Some(index) if params[index].is_rest() => format!(
+                "{} can't take its rest parameter '{}' by name",
+                function, name
+            ),
+            Some(index) if bound[index].is_some() => format!(
+                "{} got parameter '{}' both by position and by name",
+                function, name
+            ),
+            Some(index) => {
+                bound[index] = Some(value);
+                continue;
+            }
+            None => format!("{} has no parameter named '{}'", function, name),
+        };
+        return Err(ArgumentError {
+            named: Some(position),
+            message,
+        });
+    }
+
+    let missing: Vec<String> = params
+        .iter()
+        .zip(&bound)
+        .filter(|(param, value)| value.is_none() && !param.has_default())
+        .map(|(param, _)| format!("'{}'", param.name()))
+        .collect();
+    if !missing.is_empty() {
+        let noun = match missing.len() {
+            1 => "an argument for parameter",
+            _ => "arguments for parameters",
+        };
+        return Err(ArgumentError {
+            named: None,
+            message: format!("{} is missing {} {}", function, noun, missing.join(", ")),
+        });
+    }
+    Ok(bound)
+}
@@ -0,0 +1,105 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{
+    lox_scan_error,
+    parser::{parser::Parser, statement::Statement},
+    scanner::scanner::Scanner,
+    utils::namespace::Namespace,
+};
+
+/// Bookkeeping for `import`: modules already run, keyed by canonical path so
+/// two spellings of one file share it, and the chain of imports in progress.
+#[derive(Default)]
+pub struct ModuleLoader {
+    cache: HashMap<PathBuf, Rc<Namespace>>,
+    loading: Vec<PathBuf>,
+}
+
+impl ModuleLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `requested` as written in the import, relative to the directory of
+    /// `importer` or to the working directory outside of any file.
+    pub fn resolve(&self, requested: &str, importer: Option<&Path>) -> Result<PathBuf, String> {
+        let base = importer.and_then(Path::parent).unwrap_or(Path::new(""));
+        match fs::canonicalize(base.join(requested)) {
+            Ok(path) => Ok(path),
+            Err(error) => Err(format!("Cannot import '{}': {}", requested, error)),
+        }
+    }
+
+    pub fn cached(&self, path: &Path) -> Option<Rc<Namespace>> {
+        self.cache.get(path).cloned()
+    }
+
+    //Marks `path` as running, failing if it is already further up the chain
+    pub fn enter(&mut self, path: &Path) -> Result<(), String> {
+        if let Some(start) = self.loading.iter().position(|loading| loading == path) {
+            let chain: Vec<String> = self.loading[start..]
+                .iter()
+                .chain(std::iter::once(&path.to_path_buf()))
+                .map(|path| display_name(path))
+                .collect();
+            return Err(format!("Import cycle: {}", chain.join(" -> ")));
+        }
+        self.loading.push(path.to_path_buf());
+        Ok(())
+    }
+
+    //Done running `path`, a module that failed to load is not cached
+    pub fn exit(&mut self, path: &Path, namespace: Option<Rc<Namespace>>) {
+        self.loading.pop();
+        if let Some(namespace) = namespace {
+            self.cache.insert(path.to_path_buf(), namespace);
+        }
+    }
+}
+
+/// Reads and parses a module. Scanner and parser errors are reported and
+/// abort the import, like they stop the main script from running.
+pub fn load(path: &Path) -> Result<Vec<Statement>, String> {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(error) => return Err(format!("Cannot import '{}': {}", display_name(path), error)),
+    };
+    let mut scanner = Scanner::new(source);
+    let tokens = scanner.scan_tokens();
+    let syntax_errors = || {
+        format!(
+            "Cannot import '{}': it has syntax errors",
+            display_name(path)
+        )
+    };
+    if scanner.had_error() {
+        for error in scanner.errors() {
+            lox_scan_error(error);
+        }
+        return Err(syntax_errors());
+    }
+    //Parser errors are reported as they are found
+    let mut parser = Parser::new(tokens);
+    match parser.parse() {
+        Ok(statements) if !parser.had_error() => Ok(statements),
+        _ => Err(syntax_errors()),
+    }
+}
+
+//The namespace a module gets, "lib/util.lox" is util
+pub fn module_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn display_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
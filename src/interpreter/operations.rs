@@ -0,0 +1,237 @@
+//What the operators, indexing and member access do to values, shared by
+//the tree-walker and the bytecode VM so both backends behave the same
+
+use crate::{
+    natives::{list, string},
+    scanner::{token::Token, token_type::TokenType},
+    utils::{literal_value::LiteralValue, lox_error::ErrorKind, lox_map::MapKey},
+};
+
+//What went wrong, the caller decides where to raise it
+pub type Failure = (ErrorKind, String);
+
+pub fn unary(operator: TokenType, right: LiteralValue) -> Result<LiteralValue, Failure> {
+    match operator {
+        TokenType::Bang => Ok(LiteralValue::Boolean(!is_truthy(&right))),
+        TokenType::Minus => match right {
+            LiteralValue::Float(value) => Ok(LiteralValue::Float(-value)),
+            LiteralValue::None => Err((
+                ErrorKind::Type,
+                "None value cannot be used, that is really strange".to_string(),
+            )),
+            other => Err((
+                ErrorKind::Type,
+                format!("Tried to negate {}", other.type_name()),
+            )),
+        },
+        _ => Ok(LiteralValue::Nil),
+    }
+}
+
+//`operator` picks the operation, `site` is the token named in errors
+pub fn binary(
+    operator: TokenType,
+    site: &Token,
+    left: LiteralValue,
+    right: LiteralValue,
+    checked_division: bool,
+) -> Result<LiteralValue, Failure> {
+    match operator {
+        //ARITMETICHS
+        TokenType::Slash | TokenType::Percent | TokenType::TildeSlash
+            if checked_division && right == LiteralValue::Float(0.0) =>
+        {
+            Err((ErrorKind::ZeroDivision, "Division by zero".to_string()))
+        }
+        TokenType::Minus => arithmetic(site, left, right, |lv, rv| lv - rv),
+        TokenType::Star => arithmetic(site, left, right, |lv, rv| lv * rv),
+        TokenType::Slash => arithmetic(site, left, right, |lv, rv| lv / rv),
+        //Floored, so the result takes the sign of the divisor like ~/ does
+        TokenType::Percent => arithmetic(site, left, right, |lv, rv| lv - rv * (lv / rv).floor()),
+        TokenType::TildeSlash => arithmetic(site, left, right, |lv, rv| (lv / rv).floor()),
+        TokenType::StarStar => arithmetic(site, left, right, |lv, rv| lv.powf(rv)),
+        TokenType::Plus => match (left, right) {
+            (LiteralValue::String(lv), LiteralValue::String(rv)) => {
+                Ok(LiteralValue::String(format!("{}{}", lv, rv)))
+            }
+            (LiteralValue::Float(lv), LiteralValue::Float(rv)) => Ok(LiteralValue::Float(lv + rv)),
+            _ => Err((
+                ErrorKind::Type,
+                "Addition can be done between string and numbers".to_string(),
+            )),
+        },
+
+        //BOOLEAN OPERATORS
+        TokenType::LessEqual => comparison(site, left, right, |lv, rv| lv <= rv),
+        TokenType::Less => comparison(site, left, right, |lv, rv| lv < rv),
+        TokenType::Greater => comparison(site, left, right, |lv, rv| lv > rv),
+        TokenType::GreaterEqual => comparison(site, left, right, |lv, rv| lv >= rv),
+        TokenType::BangEqual => Ok(LiteralValue::Boolean(!is_equal(&left, &right))),
+        TokenType::EqualEqual => Ok(LiteralValue::Boolean(is_equal(&left, &right))),
+        _ => unreachable!(
+            "binary operations are only built from binary operators, got {}",
+            site.lexame
+        ),
+    }
+}
+
+fn arithmetic(
+    operator: &Token,
+    left: LiteralValue,
+    right: LiteralValue,
+    op: fn(f64, f64) -> f64,
+) -> Result<LiteralValue, Failure> {
+    match (left, right) {
+        (LiteralValue::Float(lv), LiteralValue::Float(rv)) => Ok(LiteralValue::Float(op(lv, rv))),
+        (lv, rv) => Err(operand_error(operator, lv, rv)),
+    }
+}
+
+fn comparison(
+    operator: &Token,
+    left: LiteralValue,
+    right: LiteralValue,
+    op: fn(f64, f64) -> bool,
+) -> Result<LiteralValue, Failure> {
+    match (left, right) {
+        (LiteralValue::Float(lv), LiteralValue::Float(rv)) => Ok(LiteralValue::Boolean(op(lv, rv))),
+        (lv, rv) => Err(operand_error(operator, lv, rv)),
+    }
+}
+
+fn operand_error(operator: &Token, left: LiteralValue, right: LiteralValue) -> Failure {
+    (
+        ErrorKind::Type,
+        format!(
+            "Operands of '{}' must be numbers, got {} and {}",
+            operator.lexame,
+            left.type_name(),
+            right.type_name()
+        ),
+    )
+}
+
+pub fn index(object: LiteralValue, index: &LiteralValue) -> Result<LiteralValue, Failure> {
+    match object {
+        LiteralValue::List(elements) => {
+            let elements = elements.borrow();
+            let position = resolve_index(index, elements.len())?;
+            Ok(elements[position].clone())
+        }
+        LiteralValue::String(text) => {
+            let characters: Vec<char> = text.chars().collect();
+            let position = resolve_index(index, characters.len())?;
+            Ok(LiteralValue::String(characters[position].to_string()))
+        }
+        LiteralValue::Range(range) => {
+            let position = resolve_index(index, range.len())?;
+            Ok(LiteralValue::Float(range.get(position)))
+        }
+        LiteralValue::Map(entries) => match MapKey::from_value(index) {
+            Ok(key) => match entries.borrow().get(&key) {
+                Some(value) => Ok(value.clone()),
+                None => Err((ErrorKind::Key, format!("Key not found: {}", index))),
+            },
+            Err(message) => Err((ErrorKind::Type, message)),
+        },
+        other => Err((
+            ErrorKind::Type,
+            format!("Cannot index into {}", other.type_name()),
+        )),
+    }
+}
+
+pub fn set_index(
+    object: LiteralValue,
+    index: &LiteralValue,
+    value: LiteralValue,
+) -> Result<LiteralValue, Failure> {
+    match object {
+        LiteralValue::List(elements) => {
+            let mut elements = elements.borrow_mut();
+            let len = elements.len();
+            let position = resolve_index(index, len)?;
+            elements[position] = value.clone();
+            Ok(value)
+        }
+        LiteralValue::Map(entries) => match MapKey::from_value(index) {
+            Ok(key) => {
+                entries.borrow_mut().insert(key, value.clone());
+                Ok(value)
+            }
+            Err(message) => Err((ErrorKind::Type, message)),
+        },
+        other => Err((
+            ErrorKind::Type,
+            format!("Cannot assign into {}", other.type_name()),
+        )),
+    }
+}
+
+//A non-integer index is a TypeError, an integer out of range an IndexError
+fn resolve_index(index: &LiteralValue, len: usize) -> Result<usize, Failure> {
+    list::resolve_index(index, len).map_err(|message| {
+        let kind = match index {
+            LiteralValue::Float(number) if number.fract() == 0.0 => ErrorKind::Index,
+            _ => ErrorKind::Type,
+        };
+        (kind, message)
+    })
+}
+
+//object.name, for the values that have members
+pub fn get_member(object: &LiteralValue, name: &str) -> Result<LiteralValue, Failure> {
+    let member = match object {
+        LiteralValue::String(_) => string::method(name).map(|method| LiteralValue::BoundMethod {
+            receiver: Box::new(object.clone()),
+            method,
+        }),
+        LiteralValue::Namespace(namespace) => namespace.get(name),
+        LiteralValue::Error(error) => match name {
+            "message" => Some(LiteralValue::String(error.message.clone())),
+            "kind" => Some(LiteralValue::String(error.kind.clone())),
+            "line" => Some(match error.line() {
+                Some(line) => LiteralValue::Float(line as f64),
+                None => LiteralValue::Nil,
+            }),
+            _ => None,
+        },
+        other => {
+            return Err((
+                ErrorKind::Type,
+                format!("Cannot read property '{}' of {}", name, other.type_name()),
+            ))
+        }
+    };
+    match member {
+        Some(member) => Ok(member),
+        None => {
+            let owner = match object {
+                LiteralValue::Namespace(namespace) => namespace.name.clone(),
+                other => other.type_name().to_string(),
+            };
+            Err((
+                ErrorKind::Attribute,
+                format!("{} has no member '{}'", owner, name),
+            ))
+        }
+    }
+}
+
+pub fn is_truthy(value: &LiteralValue) -> bool {
+    match value {
+        LiteralValue::Boolean(value) => *value,
+        LiteralValue::Nil | LiteralValue::None => false,
+        _ => true,
+    }
+}
+
+pub fn is_equal(lv: &LiteralValue, rv: &LiteralValue) -> bool {
+    if *lv == LiteralValue::None && *rv == LiteralValue::None {
+        return true;
+    }
+    if *lv == LiteralValue::None {
+        return false;
+    }
+    return lv == rv;
+}
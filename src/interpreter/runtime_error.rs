@@ -0,0 +1,53 @@
+use std::fmt;
+
+use crate::{scanner::token::Token, utils::literal_value::LiteralValue};
+
+use super::call_frame::CallFrame;
+
+/// A throw no `catch` handled, what `Interpreter::interpet` gives back.
+#[derive(Debug, Clone)]
+pub struct RuntimeError {
+    //The thrown value, an error object for the interpreter's own errors
+    pub value: LiteralValue,
+    //Where it was raised
+    pub token: Token,
+    //Calls active when it was raised, innermost first
+    pub trace: Vec<CallFrame>,
+}
+
+impl RuntimeError {
+    pub fn message(&self) -> String {
+        match &self.value {
+            LiteralValue::Error(error) => error.message.clone(),
+            other => format!("Uncaught {}", other),
+        }
+    }
+
+    //The trace as lines, a run of identical frames as in deep recursion
+    //shown once with how often it repeats
+    pub fn traceback(&self) -> Vec<String> {
+        let mut lines: Vec<String> = Vec::new();
+        let mut frames = self.trace.iter().map(|frame| frame.to_string()).peekable();
+        while let Some(frame) = frames.next() {
+            let mut repeats = 0;
+            while frames.next_if_eq(&frame).is_some() {
+                repeats += 1;
+            }
+            lines.push(frame);
+            if repeats > 0 {
+                lines.push(format!("... repeated {} more times", repeats));
+            }
+        }
+        lines
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[line {}] {}", self.token.line, self.message())?;
+        for line in self.traceback() {
+            write!(f, "\n    {}", line)?;
+        }
+        Ok(())
+    }
+}
@@ -1,22 +1,34 @@
-use core::panic;
-use interpreter::interpreter::Interpreter;
+#![allow(clippy::needless_return, clippy::module_inception)]
+
+use interpreter::interpreter::{Interpreter, DEFAULT_MAX_DEPTH};
+use interpreter::runtime_error::RuntimeError;
 use parser::parser::Parser;
+use parser::statement::Statement;
+use scanner::scan_error::ScanError;
 use scanner::scanner::Scanner;
 use scanner::token::Token;
 use scanner::token_type::TokenType;
 use std::fmt::Result;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{prelude::*, stdin};
-use std::{env, io, usize};
-use utils::literal_value::LiteralValue;
+use std::path::Path;
+use std::rc::Rc;
+use std::{env, io, process, thread};
+use vm::{compiler, disassembler, function::FunctionProto, loxc, vm::Vm};
 
 pub mod interpreter;
+pub mod natives;
 pub mod parser;
 pub mod scanner;
 pub mod utils;
+pub mod vm;
 
+//`whr` is empty for errors only known by their line
 fn report(line: usize, whr: String, message: String) {
-    println!("[line {}] Error {}: {}", line, whr, message)
+    match whr.is_empty() {
+        true => println!("[line {}] Error: {}", line, message),
+        false => println!("[line {}] Error {}: {}", line, whr, message),
+    }
 }
 
 fn report_panic(line: usize, whr: String, message: String) {
@@ -27,39 +39,187 @@ fn loxerror(line: usize, message: String) {
     report(line, String::new(), message)
 }
 
+fn lox_scan_error(error: ScanError) {
+    loxerror(error.line, error.message)
+}
+
 fn lox_parser_error(token: Token, message: String) {
     match token.token_type {
         TokenType::Eof => report(token.line, "at end".to_string(), message),
         _ => report(token.line, format!("at '{}'", token.lexame), message),
     }
 }
-fn lox_runtime_error(token: Token, message: String) -> LiteralValue {
+fn lox_runtime_error(token: Token, message: String) {
     match token.token_type {
         TokenType::Eof => report_panic(token.line, "at end".to_string(), message),
         _ => report_panic(token.line, format!("at '{}'", token.lexame), message),
     }
-    return LiteralValue::None;
 }
 
-fn run(code: String, is_ast: bool) {
+//Rust stack a single Lox call may take on the tree-walker, generous enough
+//for debug builds
+const CALL_STACK_SIZE: usize = 128 * 1024;
+
+//Largest stack asked for, which bounds --max-depth on the tree-walker
+const MAX_STACK_SIZE: usize = 1024 * 1024 * 1024;
+
+//Least stack any command gets. Parsing, compiling and dropping deeply
+//nested values recurse too, also on the VM, which keeps Lox calls on the
+//heap
+const STACK_SIZE: usize = DEFAULT_MAX_DEPTH * CALL_STACK_SIZE;
+
+//Which of the two implementations runs the program
+#[derive(Clone, Copy, PartialEq)]
+enum Backend {
+    Tree,
+    Vm,
+}
+
+//Command line switches, accepted as --name anywhere on the command line
+struct Options {
+    backend: Backend,
+    checked_division: bool,
+    seed: Option<u64>,
+    max_depth: usize,
+    trace: bool,
+}
+
+impl Options {
+    fn parse(flags: &[String]) -> Option<Options> {
+        let mut options = Options {
+            backend: Backend::Tree,
+            checked_division: true,
+            seed: None,
+            max_depth: DEFAULT_MAX_DEPTH,
+            trace: false,
+        };
+        for flag in flags {
+            match flag.as_str() {
+                "--unchecked-division" => options.checked_division = false,
+                "--backend=tree" => options.backend = Backend::Tree,
+                "--backend=vm" => options.backend = Backend::Vm,
+                "--trace" => options.trace = true,
+                _ if flag.starts_with("--seed=") => match flag["--seed=".len()..].parse() {
+                    Ok(seed) => options.seed = Some(seed),
+                    Err(_) => return None,
+                },
+                _ if flag.starts_with("--max-depth=") => {
+                    match flag["--max-depth=".len()..].parse() {
+                        Ok(depth) if depth > 0 => options.max_depth = depth,
+                        _ => return None,
+                    }
+                }
+                _ => return None,
+            }
+        }
+        //Only the VM runs instructions to trace
+        if options.trace {
+            options.backend = Backend::Vm;
+        }
+        Some(options)
+    }
+
+    fn interpreter(&self) -> Interpreter {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_checked_division(self.checked_division);
+        interpreter.set_filesystem(true);
+        interpreter.set_max_depth(self.max_depth);
+        if let Some(seed) = self.seed {
+            interpreter.set_seed(seed);
+        }
+        interpreter
+    }
+
+    fn vm(&self) -> Vm {
+        let mut vm = Vm::new();
+        vm.set_checked_division(self.checked_division);
+        vm.set_filesystem(true);
+        vm.set_max_depth(self.max_depth);
+        vm.set_trace(self.trace);
+        if let Some(seed) = self.seed {
+            vm.set_seed(seed);
+        }
+        vm
+    }
+}
+
+fn report_runtime_error(error: &RuntimeError) {
+    lox_runtime_error(error.token.clone(), error.message());
+    for line in error.traceback() {
+        println!("    {}", line);
+    }
+}
+
+//Scans and parses a program, reporting what is wrong with it. With `partial`
+//the statements that did parse are kept when others had errors
+fn parse(code: String, partial: bool) -> Option<Vec<Statement>> {
     let mut scanner = Scanner::new(code);
     let tokens: Vec<Token> = scanner.scan_tokens();
+    if scanner.had_error() {
+        for error in scanner.errors() {
+            lox_scan_error(error);
+        }
+        return None;
+    }
 
     let mut parser: Parser = Parser::new(tokens);
     match parser.parse() {
-        Ok(statements) => {
-            let mut interpreter: Interpreter = Interpreter::new();
-            interpreter.interpet(statements);
-        }
+        Ok(_) if parser.had_error() && !partial => None,
+        Ok(statements) => Some(statements),
         Err(error) => {
             parser.sync();
-            lox_parser_error(error.token, error.message)
+            lox_parser_error(*error.token, error.message);
+            None
         }
     }
 }
 
-//From source mode
-fn run_file(path: &String, is_ast: bool) -> Result {
+//Compiles a program for the VM, reporting what is wrong with it
+fn compile(code: String, partial: bool) -> Option<FunctionProto> {
+    let statements = parse(code, partial)?;
+    match compiler::compile(&statements) {
+        Ok(script) => Some(script),
+        Err(error) => {
+            match error.token {
+                Some(token) => lox_parser_error(*token, error.message),
+                None => loxerror(error.line, error.message),
+            }
+            None
+        }
+    }
+}
+
+fn run(code: String, _is_ast: bool, path: Option<&Path>, options: &Options) {
+    if options.backend == Backend::Vm {
+        if let Some(script) = compile(code, true) {
+            run_vm(script, path, options);
+        }
+        return;
+    }
+
+    let Some(statements) = parse(code, true) else {
+        return;
+    };
+    let mut interpreter: Interpreter = options.interpreter();
+    if let Some(path) = path {
+        interpreter.set_script(path);
+    }
+    if let Err(error) = interpreter.interpet(statements) {
+        report_runtime_error(&error);
+    }
+}
+
+fn run_vm(script: FunctionProto, path: Option<&Path>, options: &Options) {
+    let mut vm = options.vm();
+    if let Some(path) = path {
+        vm.set_script(path);
+    }
+    if let Err(error) = vm.run(Rc::new(script)) {
+        report_runtime_error(&error);
+    }
+}
+
+fn read_source(path: &String) -> String {
     let file_result = File::open(path);
     let mut file_result: File = match file_result {
         Ok(file) => file,
@@ -68,13 +228,74 @@ fn run_file(path: &String, is_ast: bool) -> Result {
     let mut content: String = String::new();
 
     match file_result.read_to_string(&mut content) {
-        Ok(_) => Ok(run(content, is_ast)),
+        Ok(_) => content,
         Err(_) => panic!("rlox:: Problem reading content of the file"),
     }
 }
 
+//From source mode
+fn run_file(path: &String, is_ast: bool, options: &Options) -> Result {
+    run(read_source(path), is_ast, Some(Path::new(path)), options);
+    Ok(())
+}
+
+//Prints the bytecode the VM would run for a source file or a .loxc file
+fn disassemble_file(path: &String) -> Result {
+    let script = match Path::new(path).extension().is_some_and(|ext| ext == "loxc") {
+        true => match loxc::read(&read_compiled(path)) {
+            Ok(script) => script,
+            Err(message) => {
+                println!("Error loading {}: {}", path, message);
+                return Ok(());
+            }
+        },
+        false => match compile(read_source(path), false) {
+            Some(script) => script,
+            None => return Ok(()),
+        },
+    };
+    print!("{}", disassembler::disassemble(&script, "<script>"));
+    Ok(())
+}
+
+//Whether the command runs Lox code on the tree-walker, the only part that
+//takes Rust stack for every Lox call
+fn walks_tree(args: &[String], options: &Options) -> bool {
+    let subcommand =
+        args.len() == 3 && matches!(args[1].as_str(), "compile" | "disassemble" | "run");
+    options.backend == Backend::Tree && !subcommand
+}
+
+//Writes the bytecode of a file to `output` for `lox run`. Nothing is
+//written for a file with errors, and the process exits with 65 like clox
+fn compile_file(path: &String, output: &Path) -> Result {
+    let Some(script) = compile(read_source(path), false) else {
+        process::exit(65);
+    };
+    if fs::write(output, loxc::write(&script)).is_err() {
+        panic!("rlox:: Problem writing the compiled file");
+    }
+    Ok(())
+}
+
+fn read_compiled(path: &String) -> Vec<u8> {
+    match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(_) => panic!("rlox:: Problem opening the file"),
+    }
+}
+
+//From a file written by `lox compile`, imports are found next to it
+fn run_compiled(path: &String, options: &Options) -> Result {
+    match loxc::read(&read_compiled(path)) {
+        Ok(script) => run_vm(script, Some(Path::new(path)), options),
+        Err(message) => println!("Error loading {}: {}", path, message),
+    }
+    Ok(())
+}
+
 //From prompt shell
-fn run_prompt() -> Result {
+fn run_prompt(options: &Options) -> Result {
     loop {
         print!(">> ");
         io::stdout().flush().expect("rlox:: Failed to flush stdout");
@@ -84,7 +305,7 @@ fn run_prompt() -> Result {
                 if n == 1 {
                     break Ok(());
                 }
-                run(input, false);
+                run(input, false, None, options);
             }
             Err(_) => panic!("rlox:: Problem reading input"),
         }
@@ -92,16 +313,58 @@ fn run_prompt() -> Result {
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-
-    let ast_test_key = &"ast";
-    if args.len() > 3 {
-        println!("Usage: lox [source]");
-    } else if args.len() == 2 {
-        let _ = run_file(&args[1], false);
-    } else if args.len() == 3 && args[2] == ast_test_key.to_string() {
-        let _ = run_file(&args[1], true);
-    } else {
-        let _ = run_prompt();
+    let (flags, args): (Vec<String>, Vec<String>) =
+        env::args().partition(|arg| arg.starts_with("--"));
+    let usage = "Usage: lox [--backend=tree|vm] [--trace] [--unchecked-division] [--seed=N] \
+                 [--max-depth=N] [source]\n       lox disassemble source|compiled.loxc\n       \
+                 lox compile source [-o output.loxc]\n       lox [options] run output.loxc";
+    let options = match Options::parse(&flags) {
+        Some(options) => options,
+        None => {
+            println!("{}", usage);
+            return;
+        }
+    };
+
+    //The main thread's stack only fits a hundred or so nested Lox calls
+    let stack_size = match walks_tree(&args, &options) {
+        true if options.max_depth > MAX_STACK_SIZE / CALL_STACK_SIZE => {
+            println!(
+                "--max-depth can be at most {} with --backend=tree\n{}",
+                MAX_STACK_SIZE / CALL_STACK_SIZE,
+                usage
+            );
+            return;
+        }
+        true => (options.max_depth * CALL_STACK_SIZE).max(STACK_SIZE),
+        false => STACK_SIZE,
+    };
+    let runner = thread::Builder::new()
+        .stack_size(stack_size)
+        .spawn(move || {
+            let ast_test_key = &"ast";
+            if args.len() == 5 && args[1] == "compile" && args[3] == "-o" {
+                let _ = compile_file(&args[2], Path::new(&args[4]));
+            } else if args.len() > 3 {
+                println!("{}", usage);
+            } else if args.len() == 3 && args[1] == "disassemble" {
+                let _ = disassemble_file(&args[2]);
+            } else if args.len() == 3 && args[1] == "compile" {
+                let _ = compile_file(&args[2], &Path::new(&args[2]).with_extension("loxc"));
+            } else if args.len() == 3 && args[1] == "run" {
+                let _ = run_compiled(&args[2], &options);
+            } else if args.len() == 2 {
+                let _ = run_file(&args[1], false, &options);
+            } else if args.len() == 3 && args[2] == *ast_test_key {
+                let _ = run_file(&args[1], true, &options);
+            } else {
+                let _ = run_prompt(&options);
+            }
+        });
+    match runner {
+        Ok(runner) => {
+            let _ = runner.join();
+        }
+        Err(error) => println!("rlox:: Failed to start the interpreter thread: {}", error),
     }
 }
@@ -0,0 +1,34 @@
+pub mod error;
+pub mod io;
+pub mod json;
+pub mod list;
+pub mod map;
+pub mod math;
+pub mod random;
+pub mod range;
+pub mod string;
+
+use crate::utils::{namespace::Namespace, native_function::NativeFunction};
+
+//Numbers are exact integers up to 2^53 in size and skip some past it, so
+//builtins taking integers refuse larger ones instead of saturating them
+pub const MAX_EXACT_INTEGER: f64 = 9_007_199_254_740_992.0;
+
+//Every builtin the interpreter defines as a global
+pub fn globals() -> Vec<NativeFunction> {
+    let mut natives = Vec::new();
+    natives.extend(error::natives());
+    natives.extend(io::natives());
+    natives.extend(json::natives());
+    natives.extend(list::natives());
+    natives.extend(map::natives());
+    natives.extend(random::natives());
+    natives.extend(range::natives());
+    natives.extend(string::natives());
+    natives
+}
+
+//Builtins grouped under a global name, reached as `math.sqrt`
+pub fn namespaces() -> Vec<Namespace> {
+    vec![math::namespace()]
+}
@@ -0,0 +1,38 @@
+use std::rc::Rc;
+
+use crate::utils::{
+    literal_value::LiteralValue,
+    lox_error::{ErrorKind, LoxError},
+    native_function::{NativeContext, NativeFunction},
+};
+
+pub fn natives() -> Vec<NativeFunction> {
+    vec![NativeFunction::new("error", 1..=2, error)]
+}
+
+//error(message, kind?) builds an error object for throw, kind defaults to "Error"
+fn error(
+    _context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    let mut fields = Vec::new();
+    for argument in &arguments {
+        match argument {
+            LiteralValue::String(field) => fields.push(field.clone()),
+            other => {
+                return Err(format!(
+                    "error() expects strings, got {}",
+                    other.type_name()
+                ))
+            }
+        }
+    }
+    let kind = fields
+        .get(1)
+        .map_or(ErrorKind::Generic.name(), String::as_str);
+    Ok(LiteralValue::Error(Rc::new(LoxError::new(
+        kind,
+        fields[0].clone(),
+        None,
+    ))))
+}
@@ -0,0 +1,137 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{stdin, Write},
+};
+
+use crate::utils::{
+    literal_value::LiteralValue,
+    native_function::{NativeContext, NativeFunction},
+};
+
+pub fn natives() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction::new("read_line", 0..=0, read_line),
+        NativeFunction::new("read_file", 1..=1, read_file),
+        NativeFunction::new("write_file", 2..=2, write_file),
+        NativeFunction::new("append_file", 2..=2, append_file),
+        NativeFunction::new("list_dir", 1..=1, list_dir),
+        NativeFunction::new("exists", 1..=1, exists),
+    ]
+}
+
+//Every filesystem native starts here, so a sandboxed script can't touch disk
+fn path(context: &NativeContext, value: &LiteralValue, function: &str) -> Result<String, String> {
+    if !context.filesystem {
+        return Err(format!(
+            "{}() needs filesystem access, which this interpreter does not allow",
+            function
+        ));
+    }
+    match value {
+        LiteralValue::String(path) => Ok(path.clone()),
+        other => Err(format!(
+            "{}() expects a path string, got {}",
+            function,
+            other.type_name()
+        )),
+    }
+}
+
+fn failed(function: &str, path: &str, error: std::io::Error) -> String {
+    format!("{}() failed for '{}': {}", function, path, error)
+}
+
+//A line of stdin without its line break, or nil once input is exhausted
+fn read_line(
+    _context: &mut NativeContext,
+    _arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    let mut line = String::new();
+    match stdin().read_line(&mut line) {
+        Ok(0) => Ok(LiteralValue::Nil),
+        Ok(_) => {
+            let trimmed = line.strip_suffix('\n').unwrap_or(&line);
+            let trimmed = trimmed.strip_suffix('\r').unwrap_or(trimmed);
+            Ok(LiteralValue::String(trimmed.to_string()))
+        }
+        Err(error) => Err(format!("read_line() failed: {}", error)),
+    }
+}
+
+fn read_file(
+    context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    let path = path(context, &arguments[0], "read_file")?;
+    match fs::read_to_string(&path) {
+        Ok(content) => Ok(LiteralValue::String(content)),
+        Err(error) => Err(failed("read_file", &path, error)),
+    }
+}
+
+fn contents(value: &LiteralValue, function: &str) -> Result<String, String> {
+    match value {
+        LiteralValue::String(content) => Ok(content.clone()),
+        other => Err(format!(
+            "{}() expects string contents, got {}",
+            function,
+            other.type_name()
+        )),
+    }
+}
+
+fn write_file(
+    context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    let path = path(context, &arguments[0], "write_file")?;
+    let content = contents(&arguments[1], "write_file")?;
+    match fs::write(&path, content) {
+        Ok(()) => Ok(LiteralValue::Nil),
+        Err(error) => Err(failed("write_file", &path, error)),
+    }
+}
+
+//Creates the file when it does not exist yet
+fn append_file(
+    context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    let path = path(context, &arguments[0], "append_file")?;
+    let content = contents(&arguments[1], "append_file")?;
+    let written = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(content.as_bytes()));
+    match written {
+        Ok(()) => Ok(LiteralValue::Nil),
+        Err(error) => Err(failed("append_file", &path, error)),
+    }
+}
+
+//Entry names, sorted so listings don't depend on the platform
+fn list_dir(
+    context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    let path = path(context, &arguments[0], "list_dir")?;
+    let entries = fs::read_dir(&path).map_err(|error| failed("list_dir", &path, error))?;
+    let mut names = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|error| failed("list_dir", &path, error))?;
+        names.push(entry.file_name().to_string_lossy().to_string());
+    }
+    names.sort();
+    Ok(LiteralValue::list(
+        names.into_iter().map(LiteralValue::String).collect(),
+    ))
+}
+
+fn exists(
+    context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    let path = path(context, &arguments[0], "exists")?;
+    Ok(LiteralValue::Boolean(fs::metadata(path).is_ok()))
+}
@@ -0,0 +1,432 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::utils::{
+    literal_value::LiteralValue,
+    lox_map::{LoxMap, MapKey},
+    native_function::{NativeContext, NativeFunction},
+};
+
+pub fn natives() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction::new("json_parse", 1..=1, json_parse),
+        NativeFunction::new("json_stringify", 1..=2, json_stringify),
+    ]
+}
+
+//Deeper documents and values are rejected rather than risking the native
+//stack
+const MAX_DEPTH: usize = 512;
+
+//Wider indents are narrowed to this, as JSON.stringify does
+const MAX_INDENT: usize = 10;
+
+fn json_parse(
+    _context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    let text = match &arguments[0] {
+        LiteralValue::String(text) => text,
+        other => {
+            return Err(format!(
+                "json_parse() expects a string, got {}",
+                other.type_name()
+            ))
+        }
+    };
+    let mut parser = JsonParser::new(text);
+    parser.skip_whitespace();
+    let value = parser.value(0)?;
+    parser.skip_whitespace();
+    match parser.is_at_end() {
+        true => Ok(value),
+        false => Err(parser.error("unexpected text after the JSON value")),
+    }
+}
+
+//json_stringify(value) is compact, json_stringify(value, n) indents by n
+//spaces, up to MAX_INDENT
+fn json_stringify(
+    _context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    let indent = match arguments.get(1) {
+        None | Some(LiteralValue::Nil) => None,
+        Some(LiteralValue::Float(spaces)) if spaces.fract() == 0.0 && *spaces >= 0.0 => {
+            Some((*spaces as usize).min(MAX_INDENT))
+        }
+        Some(other) => {
+            return Err(format!(
+                "json_stringify() indent must be a non-negative integer, got {}",
+                other
+            ))
+        }
+    };
+    let mut writer = JsonWriter {
+        output: String::new(),
+        indent,
+        visiting: Vec::new(),
+    };
+    writer.value(&arguments[0], 0)?;
+    Ok(LiteralValue::String(writer.output))
+}
+
+struct JsonParser {
+    source: Vec<char>,
+    current: usize,
+}
+
+impl JsonParser {
+    fn new(text: &str) -> Self {
+        JsonParser {
+            source: text.chars().collect(),
+            current: 0,
+        }
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.current >= self.source.len()
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.source.get(self.current).copied()
+    }
+
+    fn error(&self, message: &str) -> String {
+        let consumed = &self.source[..self.current.min(self.source.len())];
+        let line = consumed.iter().filter(|c| **c == '\n').count() + 1;
+        let column = consumed.iter().rev().take_while(|c| **c != '\n').count() + 1;
+        format!(
+            "json_parse() {} at line {} column {}",
+            message, line, column
+        )
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.current += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.current += 1;
+                Ok(())
+            }
+            _ => Err(self.error(&format!("expected '{}'", expected))),
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<LiteralValue, String> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("nesting is too deep"));
+        }
+        match self.peek() {
+            Some('{') => self.object(depth),
+            Some('[') => self.array(depth),
+            Some('"') => Ok(LiteralValue::String(self.string()?)),
+            Some('-' | '0'..='9') => self.number(),
+            Some('t') => self.keyword("true", LiteralValue::Boolean(true)),
+            Some('f') => self.keyword("false", LiteralValue::Boolean(false)),
+            Some('n') => self.keyword("null", LiteralValue::Nil),
+            Some(_) => Err(self.error("expected a JSON value")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn keyword(&mut self, word: &str, value: LiteralValue) -> Result<LiteralValue, String> {
+        for expected in word.chars() {
+            if self.peek() != Some(expected) {
+                return Err(self.error("expected a JSON value"));
+            }
+            self.current += 1;
+        }
+        Ok(value)
+    }
+
+    fn object(&mut self, depth: usize) -> Result<LiteralValue, String> {
+        self.expect('{')?;
+        let mut entries = LoxMap::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.current += 1;
+            return Ok(LiteralValue::map(entries));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.error("expected a string key"));
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            self.skip_whitespace();
+            let value = self.value(depth + 1)?;
+            //Like most parsers, a repeated key keeps its last value
+            entries.insert(MapKey::String(key), value);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.current += 1,
+                Some('}') => {
+                    self.current += 1;
+                    return Ok(LiteralValue::map(entries));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> Result<LiteralValue, String> {
+        self.expect('[')?;
+        let mut elements = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.current += 1;
+            return Ok(LiteralValue::list(elements));
+        }
+        loop {
+            self.skip_whitespace();
+            elements.push(self.value(depth + 1)?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.current += 1,
+                Some(']') => {
+                    self.current += 1;
+                    return Ok(LiteralValue::list(elements));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut value = String::new();
+        loop {
+            let character = match self.peek() {
+                Some(character) => character,
+                None => return Err(self.error("unterminated string")),
+            };
+            self.current += 1;
+            match character {
+                '"' => return Ok(value),
+                '\\' => value.push(self.escape()?),
+                c if (c as u32) < 0x20 => return Err(self.error("control character in string")),
+                c => value.push(c),
+            }
+        }
+    }
+
+    fn escape(&mut self) -> Result<char, String> {
+        let escaped = match self.peek() {
+            Some(escaped) => escaped,
+            None => return Err(self.error("unterminated string")),
+        };
+        self.current += 1;
+        match escaped {
+            '"' => Ok('"'),
+            '\\' => Ok('\\'),
+            '/' => Ok('/'),
+            'b' => Ok('\u{8}'),
+            'f' => Ok('\u{c}'),
+            'n' => Ok('\n'),
+            'r' => Ok('\r'),
+            't' => Ok('\t'),
+            'u' => self.unicode_escape(),
+            _ => Err(self.error("invalid escape")),
+        }
+    }
+
+    //\uXXXX, where a surrogate pair spells one character outside the BMP
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex4()?;
+        let code = match high {
+            0xD800..=0xDBFF => {
+                if self.peek() != Some('\\') || self.source.get(self.current + 1) != Some(&'u') {
+                    return Err(self.error("unpaired surrogate"));
+                }
+                self.current += 2;
+                let low = self.hex4()?;
+                if !(0xDC00..=0xDFFF).contains(&low) {
+                    return Err(self.error("unpaired surrogate"));
+                }
+                0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+            }
+            0xDC00..=0xDFFF => return Err(self.error("unpaired surrogate")),
+            code => code,
+        };
+        char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = match self.peek().and_then(|c| c.to_digit(16)) {
+                Some(digit) => digit,
+                None => return Err(self.error("invalid unicode escape")),
+            };
+            code = code * 16 + digit;
+            self.current += 1;
+        }
+        Ok(code)
+    }
+
+    //-? (0 | [1-9][0-9]*) (. [0-9]+)? ([eE] [+-]? [0-9]+)?
+    fn number(&mut self) -> Result<LiteralValue, String> {
+        let start = self.current;
+        if self.peek() == Some('-') {
+            self.current += 1;
+        }
+        match self.peek() {
+            Some('0') => self.current += 1,
+            Some('1'..='9') => self.digits(),
+            _ => return Err(self.error("invalid number")),
+        }
+        if self.peek() == Some('.') {
+            self.current += 1;
+            if !matches!(self.peek(), Some('0'..='9')) {
+                return Err(self.error("invalid number"));
+            }
+            self.digits();
+        }
+        if matches!(self.peek(), Some('e' | 'E')) {
+            self.current += 1;
+            if matches!(self.peek(), Some('+' | '-')) {
+                self.current += 1;
+            }
+            if !matches!(self.peek(), Some('0'..='9')) {
+                return Err(self.error("invalid number"));
+            }
+            self.digits();
+        }
+        let text: String = self.source[start..self.current].iter().collect();
+        match text.parse() {
+            Ok(number) => Ok(LiteralValue::Float(number)),
+            Err(_) => Err(self.error("invalid number")),
+        }
+    }
+
+    fn digits(&mut self) {
+        while matches!(self.peek(), Some('0'..='9')) {
+            self.current += 1;
+        }
+    }
+}
+
+struct JsonWriter {
+    output: String,
+    indent: Option<usize>,
+    //Lists and maps on the path from the root, to catch cycles
+    visiting: Vec<*const ()>,
+}
+
+impl JsonWriter {
+    fn value(&mut self, value: &LiteralValue, depth: usize) -> Result<(), String> {
+        if depth > MAX_DEPTH {
+            return Err("json_stringify() nesting too deep".to_string());
+        }
+        match value {
+            LiteralValue::Nil => self.output.push_str("null"),
+            LiteralValue::Boolean(value) => self.output.push_str(&value.to_string()),
+            LiteralValue::Float(number) if number.is_finite() => {
+                self.output.push_str(&number.to_string())
+            }
+            LiteralValue::Float(number) => {
+                return Err(format!(
+                    "json_stringify() cannot represent the number {}",
+                    number
+                ))
+            }
+            LiteralValue::String(text) => self.string(text),
+            LiteralValue::List(elements) => {
+                self.enter(elements)?;
+                let elements = elements.borrow();
+                self.output.push('[');
+                for (index, element) in elements.iter().enumerate() {
+                    if index > 0 {
+                        self.output.push(',');
+                    }
+                    self.newline(depth + 1);
+                    self.value(element, depth + 1)?;
+                }
+                if !elements.is_empty() {
+                    self.newline(depth);
+                }
+                self.output.push(']');
+                self.visiting.pop();
+            }
+            LiteralValue::Map(entries) => {
+                self.enter(entries)?;
+                let entries = entries.borrow();
+                self.output.push('{');
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    let key = match key {
+                        MapKey::String(key) => key,
+                        other => {
+                            return Err(format!(
+                                "json_stringify() object keys must be strings, got {}",
+                                other.to_value().type_name()
+                            ))
+                        }
+                    };
+                    if index > 0 {
+                        self.output.push(',');
+                    }
+                    self.newline(depth + 1);
+                    self.string(key);
+                    self.output.push(':');
+                    if self.indent.is_some() {
+                        self.output.push(' ');
+                    }
+                    self.value(value, depth + 1)?;
+                }
+                if !entries.is_empty() {
+                    self.newline(depth);
+                }
+                self.output.push('}');
+                self.visiting.pop();
+            }
+            other => {
+                return Err(format!(
+                    "json_stringify() cannot convert a {} to JSON",
+                    other.type_name()
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    fn enter<T>(&mut self, collection: &Rc<RefCell<T>>) -> Result<(), String> {
+        let pointer = Rc::as_ptr(collection) as *const ();
+        if self.visiting.contains(&pointer) {
+            return Err("json_stringify() cannot convert a cyclic structure".to_string());
+        }
+        self.visiting.push(pointer);
+        Ok(())
+    }
+
+    fn newline(&mut self, depth: usize) {
+        if let Some(indent) = self.indent {
+            self.output.push('\n');
+            self.output.push_str(&" ".repeat(indent * depth));
+        }
+    }
+
+    fn string(&mut self, text: &str) {
+        self.output.push('"');
+        for character in text.chars() {
+            match character {
+                '"' => self.output.push_str("\\\""),
+                '\\' => self.output.push_str("\\\\"),
+                '\n' => self.output.push_str("\\n"),
+                '\r' => self.output.push_str("\\r"),
+                '\t' => self.output.push_str("\\t"),
+                '\u{8}' => self.output.push_str("\\b"),
+                '\u{c}' => self.output.push_str("\\f"),
+                c if (c as u32) < 0x20 => self.output.push_str(&format!("\\u{:04x}", c as u32)),
+                c => self.output.push(c),
+            }
+        }
+        self.output.push('"');
+    }
+}
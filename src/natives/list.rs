@@ -0,0 +1,165 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::utils::{
+    literal_value::LiteralValue,
+    native_function::{NativeContext, NativeFunction},
+};
+
+use super::MAX_EXACT_INTEGER;
+
+pub fn natives() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction::new("len", 1..=1, len),
+        NativeFunction::new("push", 2..=2, push),
+        NativeFunction::new("pop", 1..=1, pop),
+        NativeFunction::new("slice", 2..=3, slice),
+    ]
+}
+
+/// Turns a Lox index into a position in a sequence of `len` elements.
+/// Negative indexes count from the end, so -1 is the last element.
+pub fn resolve_index(index: &LiteralValue, len: usize) -> Result<usize, String> {
+    let position = match as_integer(index) {
+        Some(position) => position,
+        None if too_large(index) => {
+            return Err(format!(
+                "Index {} out of bounds for length {}",
+                describe(index),
+                len
+            ))
+        }
+        None => return Err(format!("Index must be an integer, got {}", describe(index))),
+    };
+    let resolved = match position < 0 {
+        true => position + len as i64,
+        false => position,
+    };
+    match resolved >= 0 && resolved < len as i64 {
+        true => Ok(resolved as usize),
+        false => Err(format!(
+            "Index {} out of bounds for length {}",
+            position, len
+        )),
+    }
+}
+
+//None for anything but integers of at most 2^53 in size
+fn as_integer(value: &LiteralValue) -> Option<i64> {
+    match value {
+        LiteralValue::Float(number)
+            if number.fract() == 0.0 && number.abs() <= MAX_EXACT_INTEGER =>
+        {
+            Some(*number as i64)
+        }
+        _ => None,
+    }
+}
+
+//An integer past 2^53 in size, which as_integer refuses
+fn too_large(value: &LiteralValue) -> bool {
+    matches!(value, LiteralValue::Float(number) if number.fract() == 0.0 && number.abs() > MAX_EXACT_INTEGER)
+}
+
+fn describe(value: &LiteralValue) -> String {
+    match value {
+        LiteralValue::Float(number) => number.to_string(),
+        other => other.type_name().to_string(),
+    }
+}
+
+fn as_list(value: &LiteralValue, function: &str) -> Result<Rc<RefCell<Vec<LiteralValue>>>, String> {
+    match value {
+        LiteralValue::List(elements) => Ok(elements.clone()),
+        _ => Err(format!(
+            "{}() expects a list, got {}",
+            function,
+            value.type_name()
+        )),
+    }
+}
+
+pub fn len(
+    _context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    match &arguments[0] {
+        LiteralValue::List(elements) => Ok(LiteralValue::Float(elements.borrow().len() as f64)),
+        LiteralValue::Map(entries) => Ok(LiteralValue::Float(entries.borrow().len() as f64)),
+        LiteralValue::Range(range) => Ok(LiteralValue::Float(range.len() as f64)),
+        LiteralValue::String(value) => Ok(LiteralValue::Float(value.chars().count() as f64)),
+        other => Err(format!(
+            "len() expects a list, map, range or string, got {}",
+            other.type_name()
+        )),
+    }
+}
+
+fn push(
+    _context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    let elements = as_list(&arguments[0], "push")?;
+    elements.borrow_mut().push(arguments[1].clone());
+    Ok(LiteralValue::Nil)
+}
+
+fn pop(_context: &mut NativeContext, arguments: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let elements = as_list(&arguments[0], "pop")?;
+    let popped = elements.borrow_mut().pop();
+    match popped {
+        Some(value) => Ok(value),
+        None => Err("pop() from an empty list".to_string()),
+    }
+}
+
+/// Resolves the optional `start, end` bounds of a slice over `len`
+/// elements. Negative bounds count from the end and everything is clamped
+/// like Python's slices, so the result is always a valid (maybe empty) range.
+pub fn slice_bounds(
+    function: &str,
+    bounds: &[LiteralValue],
+    len: usize,
+) -> Result<(usize, usize), String> {
+    let clamp = |bound: &LiteralValue| -> Result<usize, String> {
+        let position = match as_integer(bound) {
+            Some(position) => position,
+            //Past either end of any sequence
+            None if too_large(bound) => match bound {
+                LiteralValue::Float(number) if *number < 0.0 => return Ok(0),
+                _ => return Ok(len),
+            },
+            None => {
+                return Err(format!(
+                    "{}() bounds must be integers, got {}",
+                    function,
+                    describe(bound)
+                ))
+            }
+        };
+        let resolved = match position < 0 {
+            true => position + len as i64,
+            false => position,
+        };
+        Ok(resolved.clamp(0, len as i64) as usize)
+    };
+    let start = match bounds.first() {
+        Some(bound) => clamp(bound)?,
+        None => 0,
+    };
+    let end = match bounds.get(1) {
+        Some(bound) => clamp(bound)?,
+        None => len,
+    };
+    Ok((start, end.max(start)))
+}
+
+//slice(xs, start, end?) copies [start, end)
+fn slice(
+    _context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    let elements = as_list(&arguments[0], "slice")?;
+    let elements = elements.borrow();
+    let (start, end) = slice_bounds("slice", &arguments[1..], elements.len())?;
+    Ok(LiteralValue::list(elements[start..end].to_vec()))
+}
@@ -0,0 +1,72 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::utils::{
+    literal_value::LiteralValue,
+    lox_map::{LoxMap, MapKey},
+    native_function::{NativeContext, NativeFunction},
+};
+
+pub fn natives() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction::new("keys", 1..=1, keys),
+        NativeFunction::new("values", 1..=1, values),
+        NativeFunction::new("has", 2..=2, has),
+        NativeFunction::new("remove", 2..=2, remove),
+    ]
+}
+
+fn as_map(value: &LiteralValue, function: &str) -> Result<Rc<RefCell<LoxMap>>, String> {
+    match value {
+        LiteralValue::Map(entries) => Ok(entries.clone()),
+        _ => Err(format!(
+            "{}() expects a map, got {}",
+            function,
+            value.type_name()
+        )),
+    }
+}
+
+//Both keys() and values() follow insertion order
+fn keys(
+    _context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    let entries = as_map(&arguments[0], "keys")?;
+    let keys = entries
+        .borrow()
+        .iter()
+        .map(|(key, _)| key.to_value())
+        .collect();
+    Ok(LiteralValue::list(keys))
+}
+
+fn values(
+    _context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    let entries = as_map(&arguments[0], "values")?;
+    let values = entries
+        .borrow()
+        .iter()
+        .map(|(_, value)| value.clone())
+        .collect();
+    Ok(LiteralValue::list(values))
+}
+
+fn has(_context: &mut NativeContext, arguments: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let entries = as_map(&arguments[0], "has")?;
+    let key = MapKey::from_value(&arguments[1])?;
+    let found = entries.borrow().contains_key(&key);
+    Ok(LiteralValue::Boolean(found))
+}
+
+//Returns the removed value, or nil when the key was not there
+fn remove(
+    _context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    let entries = as_map(&arguments[0], "remove")?;
+    let key = MapKey::from_value(&arguments[1])?;
+    let removed = entries.borrow_mut().remove(&key);
+    Ok(removed.unwrap_or(LiteralValue::Nil))
+}
@@ -0,0 +1,161 @@
+use std::f64::consts;
+
+use crate::utils::{
+    literal_value::LiteralValue,
+    namespace::Namespace,
+    native_function::{NativeContext, NativeFunction},
+};
+
+pub fn namespace() -> Namespace {
+    let mut math = Namespace::new("math");
+    let functions = vec![
+        NativeFunction::new("sqrt", 1..=1, sqrt),
+        NativeFunction::new("pow", 2..=2, pow),
+        NativeFunction::new("floor", 1..=1, floor),
+        NativeFunction::new("ceil", 1..=1, ceil),
+        NativeFunction::new("round", 1..=1, round),
+        NativeFunction::new("abs", 1..=1, abs),
+        NativeFunction::new("min", 1..=usize::MAX, min),
+        NativeFunction::new("max", 1..=usize::MAX, max),
+        NativeFunction::new("sin", 1..=1, sin),
+        NativeFunction::new("cos", 1..=1, cos),
+        NativeFunction::new("tan", 1..=1, tan),
+        NativeFunction::new("log", 1..=2, log),
+        NativeFunction::new("exp", 1..=1, exp),
+        NativeFunction::new("is_nan", 1..=1, is_nan),
+    ];
+    for function in functions {
+        math.define(function.name, LiteralValue::Native(function));
+    }
+    math.define("pi", LiteralValue::Float(consts::PI));
+    math.define("e", LiteralValue::Float(consts::E));
+    math.define("inf", LiteralValue::Float(f64::INFINITY));
+    math.define("nan", LiteralValue::Float(f64::NAN));
+    math
+}
+
+fn as_number(value: &LiteralValue, function: &str) -> Result<f64, String> {
+    match value {
+        LiteralValue::Float(number) => Ok(*number),
+        other => Err(format!(
+            "{}() expects a number, got {}",
+            function,
+            other.type_name()
+        )),
+    }
+}
+
+//Applies a one argument float function, like sqrt or floor
+fn unary(
+    arguments: &[LiteralValue],
+    function: &str,
+    op: fn(f64) -> f64,
+) -> Result<LiteralValue, String> {
+    Ok(LiteralValue::Float(op(as_number(&arguments[0], function)?)))
+}
+
+fn sqrt(
+    _context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    unary(&arguments, "sqrt", f64::sqrt)
+}
+
+fn pow(_context: &mut NativeContext, arguments: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let base = as_number(&arguments[0], "pow")?;
+    let exponent = as_number(&arguments[1], "pow")?;
+    Ok(LiteralValue::Float(base.powf(exponent)))
+}
+
+fn floor(
+    _context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    unary(&arguments, "floor", f64::floor)
+}
+
+fn ceil(
+    _context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    unary(&arguments, "ceil", f64::ceil)
+}
+
+//Halves round away from zero, round(2.5) is 3 and round(-2.5) is -3
+fn round(
+    _context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    unary(&arguments, "round", f64::round)
+}
+
+fn abs(_context: &mut NativeContext, arguments: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    unary(&arguments, "abs", f64::abs)
+}
+
+//min(a, b, ...) or min(list), same for max
+fn extreme(
+    arguments: &[LiteralValue],
+    function: &str,
+    pick: fn(f64, f64) -> f64,
+) -> Result<LiteralValue, String> {
+    let values = match arguments {
+        [LiteralValue::List(elements)] => elements.borrow().clone(),
+        _ => arguments.to_vec(),
+    };
+    let mut result: Option<f64> = None;
+    for value in &values {
+        let number = as_number(value, function)?;
+        result = Some(match result {
+            Some(current) if current.is_nan() || number.is_nan() => f64::NAN,
+            Some(current) => pick(current, number),
+            None => number,
+        });
+    }
+    match result {
+        Some(number) => Ok(LiteralValue::Float(number)),
+        None => Err(format!("{}() of an empty list", function)),
+    }
+}
+
+fn min(_context: &mut NativeContext, arguments: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    extreme(&arguments, "min", f64::min)
+}
+
+fn max(_context: &mut NativeContext, arguments: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    extreme(&arguments, "max", f64::max)
+}
+
+fn sin(_context: &mut NativeContext, arguments: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    unary(&arguments, "sin", f64::sin)
+}
+
+fn cos(_context: &mut NativeContext, arguments: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    unary(&arguments, "cos", f64::cos)
+}
+
+fn tan(_context: &mut NativeContext, arguments: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    unary(&arguments, "tan", f64::tan)
+}
+
+//log(x) is the natural logarithm, log(x, base) uses the given base
+fn log(_context: &mut NativeContext, arguments: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    let number = as_number(&arguments[0], "log")?;
+    match arguments.get(1) {
+        Some(base) => Ok(LiteralValue::Float(number.log(as_number(base, "log")?))),
+        None => Ok(LiteralValue::Float(number.ln())),
+    }
+}
+
+fn exp(_context: &mut NativeContext, arguments: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    unary(&arguments, "exp", f64::exp)
+}
+
+fn is_nan(
+    _context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    Ok(LiteralValue::Boolean(
+        as_number(&arguments[0], "is_nan")?.is_nan(),
+    ))
+}
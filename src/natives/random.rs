@@ -0,0 +1,101 @@
+use crate::utils::{
+    literal_value::LiteralValue,
+    native_function::{NativeContext, NativeFunction},
+};
+
+use super::MAX_EXACT_INTEGER;
+
+pub fn natives() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction::new("random", 0..=0, random),
+        NativeFunction::new("random_int", 2..=2, random_int),
+        NativeFunction::new("choice", 1..=1, choice),
+        NativeFunction::new("shuffle", 1..=1, shuffle),
+    ]
+}
+
+fn as_integer(value: &LiteralValue, function: &str) -> Result<i64, String> {
+    match value {
+        LiteralValue::Float(number)
+            if number.fract() == 0.0 && number.abs() <= MAX_EXACT_INTEGER =>
+        {
+            Ok(*number as i64)
+        }
+        LiteralValue::Float(number) if number.fract() == 0.0 => Err(format!(
+            "{}() expects integers of at most 2^53 in size, got {}",
+            function, number
+        )),
+        LiteralValue::Float(number) => {
+            Err(format!("{}() expects integers, got {}", function, number))
+        }
+        other => Err(format!(
+            "{}() expects integers, got {}",
+            function,
+            other.type_name()
+        )),
+    }
+}
+
+//random() is uniform in [0, 1)
+fn random(
+    context: &mut NativeContext,
+    _arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    Ok(LiteralValue::Float(context.random.next_float()))
+}
+
+//random_int(a, b) includes both ends
+fn random_int(
+    context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    let low = as_integer(&arguments[0], "random_int")?;
+    let high = as_integer(&arguments[1], "random_int")?;
+    if low > high {
+        return Err(format!("random_int() range is empty, {} > {}", low, high));
+    }
+    //Both ends are at most 2^53 in size, so is every value between them
+    let offset = context.random.below(high.abs_diff(low) + 1);
+    Ok(LiteralValue::Float((low + offset as i64) as f64))
+}
+
+fn choice(
+    context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    let elements = match &arguments[0] {
+        LiteralValue::List(elements) => elements.borrow(),
+        other => {
+            return Err(format!(
+                "choice() expects a list, got {}",
+                other.type_name()
+            ))
+        }
+    };
+    if elements.is_empty() {
+        return Err("choice() from an empty list".to_string());
+    }
+    let position = context.random.below(elements.len() as u64) as usize;
+    Ok(elements[position].clone())
+}
+
+//Fisher-Yates, in place
+fn shuffle(
+    context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    let mut elements = match &arguments[0] {
+        LiteralValue::List(elements) => elements.borrow_mut(),
+        other => {
+            return Err(format!(
+                "shuffle() expects a list, got {}",
+                other.type_name()
+            ))
+        }
+    };
+    for last in (1..elements.len()).rev() {
+        let other = context.random.below(last as u64 + 1) as usize;
+        elements.swap(last, other);
+    }
+    Ok(LiteralValue::Nil)
+}
@@ -0,0 +1,35 @@
+use crate::utils::{
+    literal_value::LiteralValue,
+    lox_range::LoxRange,
+    native_function::{NativeContext, NativeFunction},
+};
+
+pub fn natives() -> Vec<NativeFunction> {
+    vec![NativeFunction::new("range", 1..=3, range)]
+}
+
+//range(end), range(start, end) or range(start, end, step), end excluded
+fn range(
+    _context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    let mut bounds = Vec::new();
+    for argument in &arguments {
+        match argument {
+            LiteralValue::Float(number) => bounds.push(*number),
+            other => {
+                return Err(format!(
+                    "range() expects numbers, got {}",
+                    other.type_name()
+                ))
+            }
+        }
+    }
+    let range = match bounds[..] {
+        [end] => LoxRange::new(0.0, end, 1.0),
+        [start, end] => LoxRange::new(start, end, 1.0),
+        [start, end, step] => LoxRange::new(start, end, step),
+        _ => unreachable!("arity is checked before the call"),
+    }?;
+    Ok(LiteralValue::Range(range))
+}
@@ -0,0 +1,179 @@
+use crate::utils::{
+    literal_value::LiteralValue,
+    native_function::{NativeContext, NativeFunction},
+};
+
+use super::list;
+
+pub fn natives() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction::new("str", 1..=1, str),
+        NativeFunction::new("num", 1..=1, num),
+    ]
+}
+
+/// Methods reachable as `s.name(...)` on a string. The receiver is passed
+/// as the first argument and counts towards the arity.
+pub fn method(name: &str) -> Option<NativeFunction> {
+    let method = match name {
+        "len" => NativeFunction::new("len", 1..=1, list::len),
+        "upper" => NativeFunction::new("upper", 1..=1, upper),
+        "lower" => NativeFunction::new("lower", 1..=1, lower),
+        "trim" => NativeFunction::new("trim", 1..=1, trim),
+        "split" => NativeFunction::new("split", 2..=2, split),
+        "find" => NativeFunction::new("find", 2..=2, find),
+        "replace" => NativeFunction::new("replace", 3..=3, replace),
+        "starts_with" => NativeFunction::new("starts_with", 2..=2, starts_with),
+        "ends_with" => NativeFunction::new("ends_with", 2..=2, ends_with),
+        "contains" => NativeFunction::new("contains", 2..=2, contains),
+        "chars" => NativeFunction::new("chars", 1..=1, chars),
+        "slice" => NativeFunction::new("slice", 2..=3, slice),
+        _ => return None,
+    };
+    Some(method)
+}
+
+fn as_string(value: &LiteralValue, function: &str) -> Result<String, String> {
+    match value {
+        LiteralValue::String(value) => Ok(value.clone()),
+        other => Err(format!(
+            "{}() expects a string, got {}",
+            function,
+            other.type_name()
+        )),
+    }
+}
+
+//str(x) formats any value the way print does
+fn str(_context: &mut NativeContext, arguments: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    Ok(LiteralValue::String(arguments[0].to_string()))
+}
+
+fn num(_context: &mut NativeContext, arguments: Vec<LiteralValue>) -> Result<LiteralValue, String> {
+    match &arguments[0] {
+        LiteralValue::Float(number) => Ok(LiteralValue::Float(*number)),
+        LiteralValue::String(text) => match text.trim().parse::<f64>() {
+            Ok(number) => Ok(LiteralValue::Float(number)),
+            Err(_) => Err(format!("Cannot convert \"{}\" to a number", text)),
+        },
+        other => Err(format!("Cannot convert {} to a number", other.type_name())),
+    }
+}
+
+fn upper(
+    _context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    let text = as_string(&arguments[0], "upper")?;
+    Ok(LiteralValue::String(text.to_uppercase()))
+}
+
+fn lower(
+    _context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    let text = as_string(&arguments[0], "lower")?;
+    Ok(LiteralValue::String(text.to_lowercase()))
+}
+
+fn trim(
+    _context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    let text = as_string(&arguments[0], "trim")?;
+    Ok(LiteralValue::String(text.trim().to_string()))
+}
+
+fn split(
+    _context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    let text = as_string(&arguments[0], "split")?;
+    let separator = as_string(&arguments[1], "split")?;
+    if separator.is_empty() {
+        return Err("split() separator cannot be empty, use chars()".to_string());
+    }
+    let parts = text
+        .split(separator.as_str())
+        .map(|part| LiteralValue::String(part.to_string()))
+        .collect();
+    Ok(LiteralValue::list(parts))
+}
+
+//Character index of the first match, or -1
+fn find(
+    _context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    let text = as_string(&arguments[0], "find")?;
+    let needle = as_string(&arguments[1], "find")?;
+    let position = match text.find(needle.as_str()) {
+        Some(byte_index) => text[..byte_index].chars().count() as f64,
+        None => -1.0,
+    };
+    Ok(LiteralValue::Float(position))
+}
+
+fn replace(
+    _context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    let text = as_string(&arguments[0], "replace")?;
+    let from = as_string(&arguments[1], "replace")?;
+    let to = as_string(&arguments[2], "replace")?;
+    if from.is_empty() {
+        return Err("replace() pattern cannot be empty".to_string());
+    }
+    Ok(LiteralValue::String(text.replace(from.as_str(), &to)))
+}
+
+fn starts_with(
+    _context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    let text = as_string(&arguments[0], "starts_with")?;
+    let prefix = as_string(&arguments[1], "starts_with")?;
+    Ok(LiteralValue::Boolean(text.starts_with(prefix.as_str())))
+}
+
+fn ends_with(
+    _context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    let text = as_string(&arguments[0], "ends_with")?;
+    let suffix = as_string(&arguments[1], "ends_with")?;
+    Ok(LiteralValue::Boolean(text.ends_with(suffix.as_str())))
+}
+
+fn contains(
+    _context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    let text = as_string(&arguments[0], "contains")?;
+    let needle = as_string(&arguments[1], "contains")?;
+    Ok(LiteralValue::Boolean(text.contains(needle.as_str())))
+}
+
+fn chars(
+    _context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    let text = as_string(&arguments[0], "chars")?;
+    let characters = text
+        .chars()
+        .map(|character| LiteralValue::String(character.to_string()))
+        .collect();
+    Ok(LiteralValue::list(characters))
+}
+
+//s.slice(start, end?) by character index, bounds behave like list slices
+fn slice(
+    _context: &mut NativeContext,
+    arguments: Vec<LiteralValue>,
+) -> Result<LiteralValue, String> {
+    let characters: Vec<char> = as_string(&arguments[0], "slice")?.chars().collect();
+    let (start, end) = list::slice_bounds("slice", &arguments[1..], characters.len())?;
+    Ok(LiteralValue::String(
+        characters[start..end].iter().collect(),
+    ))
+}
@@ -1,5 +1,6 @@
 pub mod expression;
 pub mod parse_error;
 pub mod parser;
+pub mod precedence;
 pub mod statement;
 pub mod visitor;
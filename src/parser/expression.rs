@@ -1,6 +1,6 @@
-use std::fmt;
+use std::{fmt, rc::Rc};
 
-use super::visitor::VisitorExpression;
+use super::{statement::Statement, visitor::VisitorExpression};
 use crate::scanner::token::Token;
 use crate::utils::literal_value::LiteralValue;
 
@@ -30,6 +30,101 @@ pub enum Expression {
     Variable {
         name: Token,
     },
+
+    Logical {
+        left: Box<Expression>,
+        operator: Token,
+        right: Box<Expression>,
+    },
+
+    Conditional {
+        condition: Box<Expression>,
+        then_branch: Box<Expression>,
+        else_branch: Box<Expression>,
+    },
+
+    Sequence {
+        expressions: Vec<Expression>,
+    },
+
+    List {
+        elements: Vec<Expression>,
+    },
+
+    Map {
+        brace: Token,
+        entries: Vec<(Expression, Expression)>,
+    },
+
+    Index {
+        object: Box<Expression>,
+        bracket: Token,
+        index: Box<Expression>,
+    },
+
+    SetIndex {
+        object: Box<Expression>,
+        bracket: Token,
+        index: Box<Expression>,
+        value: Box<Expression>,
+    },
+
+    //Named arguments come after the positional ones, in source order
+    Call {
+        callee: Box<Expression>,
+        paren: Token,
+        arguments: Vec<Expression>,
+        named: Vec<(Token, Expression)>,
+    },
+
+    Get {
+        object: Box<Expression>,
+        name: Token,
+    },
+
+    //Parts are string literals and embedded expressions, in source order
+    Interpolation {
+        parts: Vec<Expression>,
+    },
+
+    //fun (a, b) { ... } and (a, b) => a + b, whose body is one return.
+    //`keyword` is the "fun" or "(" that starts it
+    Lambda {
+        keyword: Token,
+        name: Option<Token>,
+        params: Rc<Vec<Parameter>>,
+        body: Rc<Vec<Statement>>,
+    },
+}
+
+/// A parameter of a function literal. `default` fills it when the caller
+/// leaves it out, a `rest` parameter collects the extra positional
+/// arguments into a list.
+#[derive(Clone)]
+pub struct Parameter {
+    pub name: Token,
+    pub default: Option<Expression>,
+    pub rest: bool,
+}
+
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.default, self.rest) {
+            (_, true) => write!(f, "...{}", self.name.lexame),
+            (Some(default), false) => write!(f, "{} = {}", self.name.lexame, default),
+            (None, false) => write!(f, "{}", self.name.lexame),
+        }
+    }
+}
+
+fn write_list(f: &mut fmt::Formatter<'_>, expressions: &[Expression]) -> fmt::Result {
+    for (index, expression) in expressions.iter().enumerate() {
+        if index > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", expression)?;
+    }
+    Ok(())
 }
 
 impl fmt::Display for Expression {
@@ -45,6 +140,76 @@ impl fmt::Display for Expression {
             Expression::Grouping { expression } => write!(f, "(group {})", expression),
             Expression::Variable { name } => write!(f, "Variable {}", name),
             Expression::Assign { name, value } => write!(f, "Assign {}, {}", name, value),
+            Expression::Logical {
+                left,
+                operator,
+                right,
+            } => write!(f, "Logical({} {} {})", left, operator, right),
+            Expression::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+            } => write!(
+                f,
+                "Conditional({} ? {} : {})",
+                condition, then_branch, else_branch
+            ),
+            Expression::Sequence { expressions } => {
+                write!(f, "Sequence(")?;
+                write_list(f, expressions)?;
+                write!(f, ")")
+            }
+            Expression::List { elements } => {
+                write!(f, "List[")?;
+                write_list(f, elements)?;
+                write!(f, "]")
+            }
+            Expression::Map { entries, .. } => {
+                write!(f, "Map{{")?;
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", key, value)?;
+                }
+                write!(f, "}}")
+            }
+            Expression::Index { object, index, .. } => write!(f, "Index({}[{}])", object, index),
+            Expression::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => write!(f, "SetIndex({}[{}] = {})", object, index, value),
+            Expression::Call {
+                callee,
+                arguments,
+                named,
+                ..
+            } => {
+                write!(f, "Call({}(", callee)?;
+                write_list(f, arguments)?;
+                for (index, (name, value)) in named.iter().enumerate() {
+                    if index > 0 || !arguments.is_empty() {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", name.lexame, value)?;
+                }
+                write!(f, "))")
+            }
+            Expression::Get { object, name } => write!(f, "Get({}.{})", object, name.lexame),
+            Expression::Lambda { name, params, .. } => {
+                let params: Vec<String> = params.iter().map(|param| param.to_string()).collect();
+                match name {
+                    Some(name) => write!(f, "Lambda {}({})", name.lexame, params.join(", ")),
+                    None => write!(f, "Lambda({})", params.join(", ")),
+                }
+            }
+            Expression::Interpolation { parts } => {
+                write!(f, "Interpolation(")?;
+                write_list(f, parts)?;
+                write!(f, ")")
+            }
         }
     }
 }
@@ -52,30 +217,54 @@ impl fmt::Display for Expression {
 impl Expression {
     pub fn accept<R>(&self, visitor: &mut dyn VisitorExpression<R>) -> R {
         match self {
-            Expression::Unary { operator, right } => visitor.visit(&Expression::Unary {
-                operator: operator.clone(),
-                right: right.clone(),
-            }),
+            Expression::Unary { operator, right } => visitor.visit_unary(operator, right),
             Expression::Binary {
                 left,
                 operator,
                 right,
-            } => visitor.visit(&Expression::Binary {
-                left: left.clone(),
-                operator: operator.clone(),
-                right: right.clone(),
-            }),
-            Expression::Grouping { expression } => visitor.visit(&expression),
-            Expression::Literal { value } => visitor.visit(&Expression::Literal {
-                value: value.clone(),
-            }),
-            Expression::Variable { name } => {
-                visitor.visit(&Expression::Variable { name: name.clone() })
-            }
-            Expression::Assign { name, value } => visitor.visit(&Expression::Assign {
-                name: name.clone(),
-                value: value.clone(),
-            }),
+            } => visitor.visit_binary(left, operator, right),
+            Expression::Grouping { expression } => visitor.visit_grouping(expression),
+            Expression::Literal { value } => visitor.visit_literal(value),
+            Expression::Variable { name } => visitor.visit_variable(name),
+            Expression::Assign { name, value } => visitor.visit_assign(name, value),
+            Expression::Logical {
+                left,
+                operator,
+                right,
+            } => visitor.visit_logical(left, operator, right),
+            Expression::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+            } => visitor.visit_conditional(condition, then_branch, else_branch),
+            Expression::Sequence { expressions } => visitor.visit_sequence(expressions),
+            Expression::List { elements } => visitor.visit_list(elements),
+            Expression::Map { brace, entries } => visitor.visit_map(brace, entries),
+            Expression::Index {
+                object,
+                bracket,
+                index,
+            } => visitor.visit_index(object, bracket, index),
+            Expression::SetIndex {
+                object,
+                bracket,
+                index,
+                value,
+            } => visitor.visit_set_index(object, bracket, index, value),
+            Expression::Call {
+                callee,
+                paren,
+                arguments,
+                named,
+            } => visitor.visit_call(callee, paren, arguments, named),
+            Expression::Get { object, name } => visitor.visit_get(object, name),
+            Expression::Lambda {
+                keyword,
+                name,
+                params,
+                body,
+            } => visitor.visit_lambda(keyword, name.as_ref(), params, body),
+            Expression::Interpolation { parts } => visitor.visit_interpolation(parts),
         }
     }
 }
@@ -2,7 +2,8 @@ use crate::scanner::token::Token;
 
 #[derive(Debug, Clone)]
 pub struct ParsingError {
-    pub token: Token,
+    //Boxed, parse functions return this on every path
+    pub token: Box<Token>,
     pub message: String,
 }
 
@@ -14,6 +15,9 @@ impl std::fmt::Display for ParsingError {
 
 impl ParsingError {
     pub fn new(message: String, token: Token) -> Self {
-        ParsingError { message, token }
+        ParsingError {
+            message,
+            token: Box::new(token),
+        }
     }
 }
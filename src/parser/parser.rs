@@ -1,29 +1,117 @@
+use std::rc::Rc;
+
 use crate::{
-    lox_parser_error, loxerror,
+    lox_parser_error,
     scanner::{token::Token, token_type::TokenType},
     utils::literal_value::LiteralValue,
 };
 
-use super::{expression::Expression, parse_error::ParsingError, statement::Statement};
+use super::{
+    expression::{Expression, Parameter},
+    parse_error::ParsingError,
+    precedence::{Precedence, INFIX_RULES},
+    statement::Statement,
+};
 
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    //How many loops enclose the statement being parsed, break/continue need one
+    loop_depth: usize,
+    //How many function bodies enclose it, return needs one
+    function_depth: usize,
+    //How many try statements enclose it within its function, a return in
+    //one has to come back through it so its call is not a tail call
+    try_depth: usize,
+    //Set by the first error reported, parse skips the statement it was in
+    //and goes on
+    had_error: bool,
+    //How many statements and operands enclose the one being parsed
+    nesting: usize,
 }
 
+//Deeper programs are refused, parsing, compiling and running them recurse
+//on the native stack
+const MAX_NESTING: usize = 1024;
+
 /* GRAMMAR
- *  expression     → equality ;
- *  equality       → comparison ( ( "!=" | "==" ) comparison )* ;
- *  comparison     → term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
- *  term           → factor ( ( "-" | "+" ) factor )* ;
- *  factor         → unary ( ( "/" | "*" ) unary )* ;
- *  unary          → ( "!" | "-" ) unary | primary ;
- *  primary        → NUMBER | STRING | "true" | "false" | "nil" | "(" expression ")" ;
+ *  program        → declaration* EOF ;
+ *  declaration    → funDecl | varDecl | statement ;
+ *  funDecl        → "fun" IDENTIFIER function ;
+ *  function       → "(" parameters? ")" block ;
+ *  parameters     → parameter ( "," parameter )* ;
+ *  parameter      → IDENTIFIER ( "=" assignment )? | "..." IDENTIFIER ;
+ *                   (defaults follow the plain parameters, the rest parameter comes last)
+ *  varDecl        → "var" IDENTIFIER ( "=" expression )? ";" ;
+ *  statement      → exprStmt | printStmt | block | ifStmt | whileStmt | forStmt
+ *                 | breakStmt | continueStmt | returnStmt | importStmt | throwStmt | tryStmt ;
+ *  block          → "{" declaration* "}" ;
+ *  ifStmt         → "if" "(" expression ")" statement ( "else" statement )? ;
+ *  whileStmt      → "while" "(" expression ")" statement ;
+ *  forStmt        → "for" "(" ( varDecl | exprStmt | ";" ) expression? ";" expression? ")" statement
+ *                 | "for" "(" "var"? IDENTIFIER "in" expression ")" statement ;
+ *  breakStmt      → "break" ";" ;     (only inside a loop body)
+ *  continueStmt   → "continue" ";" ;  (only inside a loop body)
+ *  returnStmt     → "return" expression? ";" ;  (only inside a function body)
+ *                   (a call returned as is or as a "?:" branch, outside of any try,
+ *                    is a tail call)
+ *  throwStmt      → "throw" expression ";" ;
+ *  tryStmt        → "try" block ( "catch" "(" IDENTIFIER ")" block )? ( "finally" block )? ;
+ *                   (at least one of catch and finally)
+ *  importStmt     → "import" STRING "as" IDENTIFIER ";"
+ *                 | "from" STRING "import" IDENTIFIER ( "," IDENTIFIER )* ";" ;
+ *                   ("as" and "from" are only keywords here, they stay usable as names)
+ *
+ *  expression     → sequence ;
+ *  sequence       → assignment ( "," assignment )* ;
+ *  assignment     → ( IDENTIFIER | call "[" expression "]" ) "=" assignment | conditional ;
+ *  conditional    → binary ( "?" expression ":" conditional )? ;
+ *  binary         → unary ( INFIX_OPERATOR binary )* ;
+ *  unary          → ( "!" | "-" ) unary | call ;
+ *                   (the operand of a prefix operator still takes "**", so -2 ** 2 is -(2 ** 2))
+ *  call           → primary ( "(" arguments? ")" | "[" expression "]" | "." IDENTIFIER )* ;
+ *  arguments      → argument ( "," argument )* ;
+ *  argument       → ( IDENTIFIER ":" )? assignment ;  (named ones after the positional ones)
+ *  primary        → NUMBER | STRING | "true" | "false" | "nil" | "(" expression ")"
+ *                 | ( INTERPOLATION expression )+ STRING
+ *                 | "[" ( assignment ( "," assignment )* ","? )? "]"
+ *                 | "{" ( entry ( "," entry )* ","? )? "}"
+ *                 | "fun" function | "(" parameters? ")" "=>" assignment ;
+ *  entry          → assignment ":" assignment ;
+ *
+ *  Binary operators are parsed by precedence climbing, their precedence and
+ *  associativity come from INFIX_RULES in precedence.rs:
+ *    or         "or"  (short-circuit, parsed into Expression::Logical)
+ *    and        "and" (short-circuit, parsed into Expression::Logical)
+ *    equality   "==" "!="
+ *    comparison ">" ">=" "<" "<="
+ *    term       "-" "+"
+ *    factor     "/" "*" "%" "~/"
+ *    exponent   "**" (right associative)
 */
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Parser {
-        Parser { tokens, current: 0 }
+        Parser {
+            tokens,
+            current: 0,
+            loop_depth: 0,
+            function_depth: 0,
+            try_depth: 0,
+            had_error: false,
+            nesting: 0,
+        }
+    }
+
+    /// Whether `parse` reported errors, in which case the statements it
+    /// returned are missing the ones that had them.
+    pub fn had_error(&self) -> bool {
+        self.had_error
+    }
+
+    fn report(&mut self, token: Token, message: String) {
+        self.had_error = true;
+        lox_parser_error(token, message);
     }
 
     pub fn parse(&mut self) -> Result<Vec<Statement>, ParsingError> {
@@ -38,11 +126,10 @@ impl Parser {
     }
 
     pub fn sync(&mut self) {
-        println!("Is syncing");
         self.advance();
         while !self.is_at_end() {
             if self.previous().token_type == TokenType::Semicolon {
-                ()
+                return;
             }
             match self.peek().token_type {
                 TokenType::Class
@@ -51,6 +138,10 @@ impl Parser {
                 | TokenType::For
                 | TokenType::While
                 | TokenType::Print
+                | TokenType::Fun
+                | TokenType::Import
+                | TokenType::Throw
+                | TokenType::Try
                 | TokenType::Return => {
                     return;
                 }
@@ -62,6 +153,10 @@ impl Parser {
     }
 
     fn declaration(&mut self) -> Result<Statement, ParsingError> {
+        if self.check(TokenType::Fun) && self.check_ahead(1, TokenType::Identifier) {
+            self.advance();
+            return self.function_declaration();
+        }
         match self.match_token_type(&[TokenType::Var]) {
             true => match self.var_declaration() {
                 Ok(r) => Ok(r),
@@ -74,6 +169,142 @@ impl Parser {
         }
     }
 
+    //fun name(...) { ... } is a variable holding a named lambda
+    fn function_declaration(&mut self) -> Result<Statement, ParsingError> {
+        let keyword = self.previous();
+        let name = self.advance();
+        let function = self.function(keyword, Some(name.clone()))?;
+        return Ok(Statement::Var {
+            name,
+            initializer: function,
+        });
+    }
+
+    //After "fun" and the name, if any
+    fn function(
+        &mut self,
+        keyword: Token,
+        name: Option<Token>,
+    ) -> Result<Expression, ParsingError> {
+        self.consume(
+            TokenType::LeftParen,
+            "Expect '(' before parameters".to_string(),
+        )?;
+        let params = self.parameters()?;
+        self.consume(
+            TokenType::LeftBrace,
+            "Expect '{' before function body".to_string(),
+        )?;
+        //Loops and trys around the function don't reach into its body
+        let loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        let try_depth = std::mem::replace(&mut self.try_depth, 0);
+        self.function_depth += 1;
+        let body = self.block();
+        self.function_depth -= 1;
+        self.loop_depth = loop_depth;
+        self.try_depth = try_depth;
+        return Ok(Expression::Lambda {
+            keyword,
+            name,
+            params: Rc::new(params),
+            body: Rc::new(body?),
+        });
+    }
+
+    //After the opening "(", up to and including the ")"
+    fn parameters(&mut self) -> Result<Vec<Parameter>, ParsingError> {
+        let mut params: Vec<Parameter> = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                let parameter = self.parameter()?;
+                let previous = params.last();
+                let misplaced = if params
+                    .iter()
+                    .any(|param| param.name.lexame == parameter.name.lexame)
+                {
+                    Some(format!("Duplicate parameter '{}'", parameter.name.lexame))
+                } else if previous.is_some_and(|param| param.rest) {
+                    Some("The rest parameter must be the last one".to_string())
+                } else if !parameter.rest
+                    && parameter.default.is_none()
+                    && previous.is_some_and(|param| param.default.is_some())
+                {
+                    Some(format!(
+                        "Parameter '{}' without a default follows one with a default",
+                        parameter.name.lexame
+                    ))
+                } else {
+                    None
+                };
+                if let Some(message) = misplaced {
+                    self.report(parameter.name.clone(), message.clone());
+                    return Err(ParsingError::new(message, parameter.name));
+                }
+                params.push(parameter);
+                if !self.match_token_type(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after parameters".to_string(),
+        )?;
+        return Ok(params);
+    }
+
+    fn parameter(&mut self) -> Result<Parameter, ParsingError> {
+        let rest = self.match_token_type(&[TokenType::Ellipsis]);
+        let name = self.consume(TokenType::Identifier, "Expect parameter name".to_string())?;
+        let default = match !rest && self.match_token_type(&[TokenType::Equal]) {
+            true => Some(self.assignment()?),
+            false => None,
+        };
+        return Ok(Parameter {
+            name,
+            default,
+            rest,
+        });
+    }
+
+    //(a, b) => a + b, the "(" already consumed
+    fn arrow_function(&mut self) -> Result<Expression, ParsingError> {
+        let keyword = self.previous();
+        let params = self.parameters()?;
+        let arrow = self.consume(TokenType::Arrow, "Expect '=>' after parameters".to_string())?;
+        let value = self.assignment()?;
+        return Ok(Expression::Lambda {
+            keyword,
+            name: None,
+            params: Rc::new(params),
+            body: Rc::new(vec![Statement::Return {
+                keyword: arrow,
+                tail: ends_in_call(&value),
+                value: Some(value),
+            }]),
+        });
+    }
+
+    //Whether the "(" at the cursor starts an arrow function rather than a
+    //grouping, that is whether "=>" follows its matching ")"
+    fn arrow_ahead(&self) -> bool {
+        let mut depth = 0;
+        for (offset, token) in self.tokens[self.current..].iter().enumerate() {
+            match token.token_type {
+                TokenType::LeftParen | TokenType::LeftBracket | TokenType::LeftBrace => depth += 1,
+                TokenType::RightParen | TokenType::RightBracket | TokenType::RightBrace => {
+                    depth -= 1
+                }
+                TokenType::Eof => return false,
+                _ => {}
+            }
+            if depth == 0 {
+                return self.check_ahead(offset + 1, TokenType::Arrow);
+            }
+        }
+        false
+    }
+
     fn var_declaration(&mut self) -> Result<Statement, ParsingError> {
         match self.consume(TokenType::Identifier, "Expect variable name".to_string()) {
             Ok(name) => match self.match_token_type(&[TokenType::Equal]) {
@@ -108,12 +339,320 @@ impl Parser {
     }
 
     fn statement(&mut self) -> Result<Statement, ParsingError> {
+        self.nested(Self::nested_statement)
+    }
+
+    fn nested_statement(&mut self) -> Result<Statement, ParsingError> {
+        if self.match_token_type(&[TokenType::For]) {
+            return self.for_statement();
+        }
+        if self.match_token_type(&[TokenType::If]) {
+            return self.if_statement();
+        }
+        if self.match_token_type(&[TokenType::While]) {
+            return self.while_statement();
+        }
+        if self.match_token_type(&[TokenType::LeftBrace]) {
+            return Ok(Statement::Block {
+                statements: self.block()?,
+            });
+        }
+        if self.match_token_type(&[TokenType::Break, TokenType::Continue]) {
+            return self.loop_jump_statement();
+        }
+        if self.match_token_type(&[TokenType::Return]) {
+            return self.return_statement();
+        }
+        if self.match_token_type(&[TokenType::Throw]) {
+            return self.throw_statement();
+        }
+        if self.match_token_type(&[TokenType::Try]) {
+            return self.try_statement();
+        }
+        if self.match_token_type(&[TokenType::Import]) {
+            return self.import_statement();
+        }
+        if self.check_word("from") && self.check_ahead(1, TokenType::String) {
+            self.advance();
+            return self.selective_import_statement();
+        }
         match self.match_token_type(&[TokenType::Print]) {
             true => self.print_statement(),
             false => self.expression_statement(),
         }
     }
 
+    fn block(&mut self) -> Result<Vec<Statement>, ParsingError> {
+        let mut statements = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            statements.push(self.declaration()?);
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after block".to_string())?;
+        return Ok(statements);
+    }
+
+    fn loop_jump_statement(&mut self) -> Result<Statement, ParsingError> {
+        let keyword = self.previous();
+        if self.loop_depth == 0 {
+            let message = format!("Can't use '{}' outside of a loop", keyword.lexame);
+            self.report(keyword.clone(), message.clone());
+            return Err(ParsingError::new(message, keyword));
+        }
+        self.consume(
+            TokenType::Semicolon,
+            format!("Expect ';' after '{}'", keyword.lexame),
+        )?;
+        return match keyword.token_type {
+            TokenType::Break => Ok(Statement::Break { keyword }),
+            _ => Ok(Statement::Continue { keyword }),
+        };
+    }
+
+    fn return_statement(&mut self) -> Result<Statement, ParsingError> {
+        let keyword = self.previous();
+        if self.function_depth == 0 {
+            let message = "Can't return from top-level code".to_string();
+            self.report(keyword.clone(), message.clone());
+            return Err(ParsingError::new(message, keyword));
+        }
+        let value = match self.check(TokenType::Semicolon) {
+            true => None,
+            false => Some(self.expression()?),
+        };
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after return value".to_string(),
+        )?;
+        let tail = self.try_depth == 0 && value.as_ref().is_some_and(ends_in_call);
+        return Ok(Statement::Return {
+            keyword,
+            value,
+            tail,
+        });
+    }
+
+    fn throw_statement(&mut self) -> Result<Statement, ParsingError> {
+        let keyword = self.previous();
+        let value = self.expression()?;
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after thrown value".to_string(),
+        )?;
+        return Ok(Statement::Throw { keyword, value });
+    }
+
+    fn try_statement(&mut self) -> Result<Statement, ParsingError> {
+        let keyword = self.previous();
+        self.consume(TokenType::LeftBrace, "Expect '{' after 'try'".to_string())?;
+        let body = self.try_block()?;
+        let catch = match self.match_token_type(&[TokenType::Catch]) {
+            true => {
+                self.consume(TokenType::LeftParen, "Expect '(' after 'catch'".to_string())?;
+                let name = self.consume(
+                    TokenType::Identifier,
+                    "Expect error variable name".to_string(),
+                )?;
+                self.consume(
+                    TokenType::RightParen,
+                    "Expect ')' after error variable".to_string(),
+                )?;
+                self.consume(
+                    TokenType::LeftBrace,
+                    "Expect '{' before catch body".to_string(),
+                )?;
+                Some((name, self.try_block()?))
+            }
+            false => None,
+        };
+        let finally = match self.match_token_type(&[TokenType::Finally]) {
+            true => {
+                self.consume(
+                    TokenType::LeftBrace,
+                    "Expect '{' after 'finally'".to_string(),
+                )?;
+                Some(self.try_block()?)
+            }
+            false => None,
+        };
+        if catch.is_none() && finally.is_none() {
+            let message = "Expect 'catch' or 'finally' after try block".to_string();
+            self.report(keyword.clone(), message.clone());
+            return Err(ParsingError::new(message, keyword));
+        }
+        return Ok(Statement::Try {
+            body,
+            catch,
+            finally,
+        });
+    }
+
+    //Any of the blocks of a try statement
+    fn try_block(&mut self) -> Result<Vec<Statement>, ParsingError> {
+        self.try_depth += 1;
+        let block = self.block();
+        self.try_depth -= 1;
+        return block;
+    }
+
+    fn import_statement(&mut self) -> Result<Statement, ParsingError> {
+        let keyword = self.previous();
+        let path = self.consume(
+            TokenType::String,
+            "Expect module path after 'import'".to_string(),
+        )?;
+        if !self.check_word("as") {
+            let token = self.peek();
+            let message = "Expect 'as' after module path".to_string();
+            self.report(token.clone(), message.clone());
+            return Err(ParsingError::new(message, token));
+        }
+        self.advance();
+        let alias = self.consume(
+            TokenType::Identifier,
+            "Expect module name after 'as'".to_string(),
+        )?;
+        self.consume(TokenType::Semicolon, "Expect ';' after import".to_string())?;
+        return Ok(Statement::Import {
+            keyword,
+            path,
+            alias,
+        });
+    }
+
+    fn selective_import_statement(&mut self) -> Result<Statement, ParsingError> {
+        let keyword = self.previous();
+        let path = self.advance();
+        self.consume(
+            TokenType::Import,
+            "Expect 'import' after module path".to_string(),
+        )?;
+        let mut names =
+            vec![self.consume(TokenType::Identifier, "Expect name to import".to_string())?];
+        while self.match_token_type(&[TokenType::Comma]) {
+            names.push(self.consume(TokenType::Identifier, "Expect name to import".to_string())?);
+        }
+        self.consume(TokenType::Semicolon, "Expect ';' after import".to_string())?;
+        return Ok(Statement::FromImport {
+            keyword,
+            path,
+            names,
+        });
+    }
+
+    fn loop_body(&mut self) -> Result<Statement, ParsingError> {
+        self.loop_depth += 1;
+        let body = self.statement();
+        self.loop_depth -= 1;
+        return body;
+    }
+
+    fn if_statement(&mut self) -> Result<Statement, ParsingError> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'".to_string())?;
+        let condition = self.expression()?;
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after if condition".to_string(),
+        )?;
+        let then_branch = self.statement()?;
+        let else_branch = match self.match_token_type(&[TokenType::Else]) {
+            true => Some(Box::new(self.statement()?)),
+            false => None,
+        };
+        return Ok(Statement::If {
+            condition,
+            then_branch: Box::new(then_branch),
+            else_branch,
+        });
+    }
+
+    fn while_statement(&mut self) -> Result<Statement, ParsingError> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'".to_string())?;
+        let condition = self.expression()?;
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after condition".to_string(),
+        )?;
+        let body = self.loop_body()?;
+        return Ok(Statement::While {
+            condition,
+            body: Box::new(body),
+            increment: None,
+        });
+    }
+
+    fn for_statement(&mut self) -> Result<Statement, ParsingError> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'".to_string())?;
+        let is_for_in = (self.check(TokenType::Identifier) && self.check_ahead(1, TokenType::In))
+            || (self.check(TokenType::Var)
+                && self.check_ahead(1, TokenType::Identifier)
+                && self.check_ahead(2, TokenType::In));
+        match is_for_in {
+            true => self.for_in_statement(),
+            false => self.c_for_statement(),
+        }
+    }
+
+    fn for_in_statement(&mut self) -> Result<Statement, ParsingError> {
+        self.match_token_type(&[TokenType::Var]);
+        let name = self.consume(
+            TokenType::Identifier,
+            "Expect loop variable name".to_string(),
+        )?;
+        self.consume(TokenType::In, "Expect 'in' after loop variable".to_string())?;
+        let iterable = self.expression()?;
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after for clauses".to_string(),
+        )?;
+        let body = self.loop_body()?;
+        return Ok(Statement::ForIn {
+            name,
+            iterable,
+            body: Box::new(body),
+        });
+    }
+
+    //Desugared to { initializer; while (condition; increment) body }
+    fn c_for_statement(&mut self) -> Result<Statement, ParsingError> {
+        let initializer = if self.match_token_type(&[TokenType::Semicolon]) {
+            None
+        } else if self.match_token_type(&[TokenType::Var]) {
+            Some(self.var_declaration()?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+        let condition = match self.check(TokenType::Semicolon) {
+            true => Expression::Literal {
+                value: LiteralValue::Boolean(true),
+            },
+            false => self.expression()?,
+        };
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after loop condition".to_string(),
+        )?;
+        let increment = match self.check(TokenType::RightParen) {
+            true => None,
+            false => Some(self.expression()?),
+        };
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after for clauses".to_string(),
+        )?;
+
+        let mut body = Statement::While {
+            condition,
+            body: Box::new(self.loop_body()?),
+            increment,
+        };
+        if let Some(initializer) = initializer {
+            body = Statement::Block {
+                statements: vec![initializer, body],
+            };
+        }
+        return Ok(body);
+    }
+
     fn print_statement(&mut self) -> Result<Statement, ParsingError> {
         return match self.expression() {
             Ok(value) => {
@@ -139,12 +678,23 @@ impl Parser {
     }
 
     fn expression(&mut self) -> Result<Expression, ParsingError> {
-        self.assignment()
+        self.sequence()
+    }
+
+    fn sequence(&mut self) -> Result<Expression, ParsingError> {
+        let first = self.assignment()?;
+        if !self.check(TokenType::Comma) {
+            return Ok(first);
+        }
+        let mut expressions = vec![first];
+        while self.match_token_type(&[TokenType::Comma]) {
+            expressions.push(self.assignment()?);
+        }
+        return Ok(Expression::Sequence { expressions });
     }
-    /**/
 
     fn assignment(&mut self) -> Result<Expression, ParsingError> {
-        match self.equality() {
+        match self.conditional() {
             Ok(equality_expression) => match self.match_token_type(&[TokenType::Equal]) {
                 true => {
                     let equals = self.previous();
@@ -154,8 +704,18 @@ impl Parser {
                                 name,
                                 value: Box::new(value),
                             }),
+                            Expression::Index {
+                                object,
+                                bracket,
+                                index,
+                            } => Ok(Expression::SetIndex {
+                                object,
+                                bracket,
+                                index,
+                                value: Box::new(value),
+                            }),
                             _ => {
-                                lox_parser_error(equals, "Invalid assignment target".to_string());
+                                self.report(equals, "Invalid assignment target".to_string());
                                 Ok(equality_expression)
                             }
                         },
@@ -168,116 +728,75 @@ impl Parser {
         }
     }
 
-    fn equality(&mut self) -> Result<Expression, ParsingError> {
-        match self.comparison() {
-            Ok(expression) => {
-                let mut mutable_expression = expression.clone();
-                while self.match_token_type(&[TokenType::EqualEqual, TokenType::BangEqual]) {
-                    let operator: Token = self.previous();
-                    match self.comparison() {
-                        Ok(right) => {
-                            mutable_expression = Expression::Binary {
-                                left: Box::new(mutable_expression),
-                                operator,
-                                right: Box::new(right),
-                            }
-                        }
-                        Err(error) => {
-                            return Err(error);
-                        }
-                    }
-                }
-                return Ok(mutable_expression);
-            }
-            Err(error) => Err(error),
+    //Right associative: a ? b : c ? d : e is a ? b : (c ? d : e)
+    fn conditional(&mut self) -> Result<Expression, ParsingError> {
+        let condition = self.binary(Precedence::Or)?;
+        if !self.match_token_type(&[TokenType::Question]) {
+            return Ok(condition);
         }
+        let then_branch = self.expression()?;
+        self.consume(
+            TokenType::Colon,
+            "Expect ':' after then branch of conditional expression".to_string(),
+        )?;
+        let else_branch = self.conditional()?;
+        return Ok(Expression::Conditional {
+            condition: Box::new(condition),
+            then_branch: Box::new(then_branch),
+            else_branch: Box::new(else_branch),
+        });
     }
 
-    fn comparison(&mut self) -> Result<Expression, ParsingError> {
-        match self.term() {
-            Ok(expression) => {
-                let mut mutable_expression = expression.clone();
-                while self.match_token_type(&[
-                    TokenType::Greater,
-                    TokenType::GreaterEqual,
-                    TokenType::Less,
-                    TokenType::LessEqual,
-                ]) {
-                    let operator: Token = self.previous();
-                    match self.term() {
-                        Ok(right) => {
-                            mutable_expression = Expression::Binary {
-                                left: Box::new(mutable_expression),
-                                operator,
-                                right: Box::new(right),
-                            }
-                        }
-                        Err(error) => {
-                            return Err(error);
-                        }
-                    }
-                }
-                return Ok(mutable_expression);
-            }
-            Err(error) => Err(error),
-        }
+    //Operands in parentheses, of prefix operators and on the right of an
+    //operator all come back here, one level deeper
+    fn binary(&mut self, min_precedence: Precedence) -> Result<Expression, ParsingError> {
+        self.nested(|parser| parser.nested_binary(min_precedence))
     }
 
-    fn term(&mut self) -> Result<Expression, ParsingError> {
-        match self.factor() {
-            Ok(expression) => {
-                let mut mutable_expression = expression.clone();
-                while self.match_token_type(&[TokenType::Minus, TokenType::Plus]) {
-                    let operator: Token = self.previous();
-                    match self.factor() {
-                        Ok(right) => {
-                            mutable_expression = Expression::Binary {
-                                left: Box::new(mutable_expression),
-                                operator,
-                                right: Box::new(right),
-                            }
-                        }
-                        Err(error) => {
-                            return Err(error);
-                        }
-                    }
-                }
-                return Ok(mutable_expression);
-            }
-            Err(error) => Err(error),
-        }
+    //Every operator continuing a left associative chain puts what came
+    //before it a level deeper too
+    fn nested_binary(&mut self, min_precedence: Precedence) -> Result<Expression, ParsingError> {
+        let nesting = self.nesting;
+        let expression = self.operators(min_precedence);
+        self.nesting = nesting;
+        expression
     }
 
-    fn factor(&mut self) -> Result<Expression, ParsingError> {
-        match self.unary() {
-            Ok(expression) => {
-                let mut mutable_expression = expression.clone();
-                while self.match_token_type(&[TokenType::Slash, TokenType::Star]) {
-                    let operator: Token = self.previous();
-                    match self.unary() {
-                        Ok(right) => {
-                            mutable_expression = Expression::Binary {
-                                left: Box::new(mutable_expression),
-                                operator,
-                                right: Box::new(right),
-                            }
-                        }
-                        Err(error) => {
-                            return Err(error);
-                        }
-                    }
-                }
-                return Ok(mutable_expression);
+    fn operators(&mut self, min_precedence: Precedence) -> Result<Expression, ParsingError> {
+        let mut expression = self.unary()?;
+        loop {
+            let rule = match INFIX_RULES.get(&self.peek().token_type) {
+                Some(rule) if rule.precedence >= min_precedence => *rule,
+                _ => break,
+            };
+            if self.nesting >= MAX_NESTING {
+                return Err(self.too_deep());
             }
-            Err(error) => Err(error),
+            let operator: Token = self.advance();
+            let right = self.binary(rule.right_operand_precedence())?;
+            expression = match operator.token_type {
+                //Short-circuiting, so kept apart from arithmetic
+                TokenType::And | TokenType::Or => Expression::Logical {
+                    left: Box::new(expression),
+                    operator,
+                    right: Box::new(right),
+                },
+                _ => Expression::Binary {
+                    left: Box::new(expression),
+                    operator,
+                    right: Box::new(right),
+                },
+            };
+            self.nesting += 1;
         }
+        return Ok(expression);
     }
 
     fn unary(&mut self) -> Result<Expression, ParsingError> {
         match self.match_token_type(&[TokenType::Bang, TokenType::Minus]) {
             true => {
                 let operator: Token = self.previous();
-                let right: Result<Expression, ParsingError> = self.unary();
+                let right: Result<Expression, ParsingError> = self.binary(Precedence::Unary);
                 match right {
                     Ok(right_expression) => Ok(Expression::Unary {
                         operator,
@@ -286,8 +805,145 @@ impl Parser {
                     Err(error) => Err(error),
                 }
             }
-            _ => return self.primary(),
+            _ => return self.call(),
+        }
+    }
+
+    fn call(&mut self) -> Result<Expression, ParsingError> {
+        let mut expression = self.primary()?;
+        loop {
+            if self.match_token_type(&[TokenType::LeftParen]) {
+                expression = self.finish_call(expression)?;
+            } else if self.match_token_type(&[TokenType::Dot]) {
+                let name = self.consume(
+                    TokenType::Identifier,
+                    "Expect property name after '.'".to_string(),
+                )?;
+                expression = Expression::Get {
+                    object: Box::new(expression),
+                    name,
+                };
+            } else if self.match_token_type(&[TokenType::LeftBracket]) {
+                let bracket = self.previous();
+                let index = self.expression()?;
+                self.consume(
+                    TokenType::RightBracket,
+                    "Expect ']' after index".to_string(),
+                )?;
+                expression = Expression::Index {
+                    object: Box::new(expression),
+                    bracket,
+                    index: Box::new(index),
+                };
+            } else {
+                break;
+            }
+            //Like a chain of operators, each one nests what came before
+            if self.nesting >= MAX_NESTING {
+                return Err(self.too_deep());
+            }
+            self.nesting += 1;
+        }
+        return Ok(expression);
+    }
+
+    fn finish_call(&mut self, callee: Expression) -> Result<Expression, ParsingError> {
+        let mut arguments = Vec::new();
+        let mut named: Vec<(Token, Expression)> = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if self.check(TokenType::Identifier) && self.check_ahead(1, TokenType::Colon) {
+                    let name = self.advance();
+                    self.advance();
+                    if named.iter().any(|(other, _)| other.lexame == name.lexame) {
+                        let message = format!("Argument '{}' is passed twice", name.lexame);
+                        self.report(name.clone(), message.clone());
+                        return Err(ParsingError::new(message, name));
+                    }
+                    named.push((name, self.assignment()?));
+                } else if let Some((name, _)) = named.last() {
+                    let message = "Positional argument after a named one".to_string();
+                    self.report(name.clone(), message.clone());
+                    return Err(ParsingError::new(message, name.clone()));
+                } else {
+                    //assignment, not expression: a comma here separates arguments
+                    arguments.push(self.assignment()?);
+                }
+                if !self.match_token_type(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        let paren = self.consume(
+            TokenType::RightParen,
+            "Expect ')' after arguments".to_string(),
+        )?;
+        return Ok(Expression::Call {
+            callee: Box::new(callee),
+            paren,
+            arguments,
+            named,
+        });
+    }
+
+    fn list(&mut self) -> Result<Expression, ParsingError> {
+        let mut elements = Vec::new();
+        while !self.check(TokenType::RightBracket) {
+            elements.push(self.assignment()?);
+            if !self.match_token_type(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        self.consume(
+            TokenType::RightBracket,
+            "Expect ']' after list elements".to_string(),
+        )?;
+        return Ok(Expression::List { elements });
+    }
+
+    fn interpolation(&mut self) -> Result<Expression, ParsingError> {
+        let mut parts = Vec::new();
+        let mut segment = self.previous();
+        loop {
+            if let LiteralValue::String(text) = &segment.literal {
+                if !text.is_empty() {
+                    parts.push(Expression::Literal {
+                        value: segment.literal.clone(),
+                    });
+                }
+            }
+            if segment.token_type == TokenType::String {
+                break;
+            }
+            parts.push(self.expression()?);
+            segment = match self.match_token_type(&[TokenType::Interpolation]) {
+                true => self.previous(),
+                false => self.consume(
+                    TokenType::String,
+                    "Expect '}' after interpolated expression".to_string(),
+                )?,
+            };
+        }
+        return Ok(Expression::Interpolation { parts });
+    }
+
+    fn map(&mut self) -> Result<Expression, ParsingError> {
+        let brace = self.previous();
+        let mut entries = Vec::new();
+        while !self.check(TokenType::RightBrace) {
+            let key = self.assignment()?;
+            self.consume(TokenType::Colon, "Expect ':' after map key".to_string())?;
+            let value = self.assignment()?;
+            entries.push((key, value));
+            if !self.match_token_type(&[TokenType::Comma]) {
+                break;
+            }
         }
+        self.consume(
+            TokenType::RightBrace,
+            "Expect '}' after map entries".to_string(),
+        )?;
+        return Ok(Expression::Map { brace, entries });
     }
 
     fn primary(&mut self) -> Result<Expression, ParsingError> {
@@ -320,6 +976,23 @@ impl Parser {
                 },
             });
         }
+        if self.match_token_type(&[TokenType::Interpolation]) {
+            return self.interpolation();
+        }
+        if self.match_token_type(&[TokenType::LeftBracket]) {
+            return self.list();
+        }
+        if self.match_token_type(&[TokenType::LeftBrace]) {
+            return self.map();
+        }
+        if self.match_token_type(&[TokenType::Fun]) {
+            let keyword = self.previous();
+            return self.function(keyword, None);
+        }
+        if self.check(TokenType::LeftParen) && self.arrow_ahead() {
+            self.advance();
+            return self.arrow_function();
+        }
         if self.match_token_type(&[TokenType::LeftParen]) {
             match self.expression() {
                 Ok(expr) => match self.consume(
@@ -338,7 +1011,7 @@ impl Parser {
         }
 
         let token = self.peek();
-        lox_parser_error(token.clone(), "Expected expression".to_string());
+        self.report(token.clone(), "Expected expression".to_string());
         return Err(ParsingError::new(
             "expected expression".to_string(),
             token.clone(),
@@ -376,28 +1049,199 @@ impl Parser {
     }
 
     fn is_at_end(&mut self) -> bool {
-        return match self.peek().token_type {
-            TokenType::Eof => true,
-            _ => false,
-        };
+        return matches!(self.peek().token_type, TokenType::Eof);
     }
 
     fn peek(&mut self) -> Token {
         return self.tokens[self.current].clone();
     }
 
+    //Looks `offset` tokens past the current one without consuming anything
+    //Contextual keywords are scanned as identifiers
+    fn check_word(&mut self, word: &str) -> bool {
+        self.check(TokenType::Identifier) && self.peek().lexame == word
+    }
+
+    fn check_ahead(&self, offset: usize, _type: TokenType) -> bool {
+        match self.tokens.get(self.current + offset) {
+            Some(token) => token.token_type == _type,
+            None => false,
+        }
+    }
+
     fn previous(&mut self) -> Token {
         return self.tokens[self.current - 1].clone();
     }
 
+    //Runs `parse` one level deeper, refusing to go past MAX_NESTING
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, ParsingError>,
+    ) -> Result<T, ParsingError> {
+        if self.nesting >= MAX_NESTING {
+            return Err(self.too_deep());
+        }
+        self.nesting += 1;
+        let result = parse(self);
+        self.nesting -= 1;
+        result
+    }
+
+    fn too_deep(&mut self) -> ParsingError {
+        let token = self.peek();
+        let message = format!("Nested more than {} deep", MAX_NESTING);
+        self.report(token.clone(), message.clone());
+        ParsingError::new(message, token)
+    }
+
     fn consume(&mut self, token_type: TokenType, message: String) -> Result<Token, ParsingError> {
         let token = self.peek();
         match self.check(token_type) {
             true => Ok(self.advance()),
             _ => {
-                lox_parser_error(token.clone(), message.clone());
+                self.report(token.clone(), message.clone());
                 Err(ParsingError::new(message, token.clone()))
             }
         }
     }
 }
+
+//Whether a returned expression can hand back the result of a call as is
+fn ends_in_call(expression: &Expression) -> bool {
+    match expression {
+        Expression::Call { .. } => true,
+        Expression::Grouping { expression } => ends_in_call(expression),
+        Expression::Conditional {
+            then_branch,
+            else_branch,
+            ..
+        } => ends_in_call(then_branch) || ends_in_call(else_branch),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::scanner::Scanner;
+
+    //The AST of a one statement program, as Display prints it
+    fn ast(source: &str) -> String {
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let statements = Parser::new(tokens).parse().expect("the source parses");
+        assert_eq!(statements.len(), 1);
+        statements[0].to_string()
+    }
+
+    #[test]
+    fn left_associative_chains_group_to_the_left() {
+        assert_eq!(
+            ast("1 - 2 - 3;"),
+            "Binary(Binary(Literal 1 Minus - Literal 2) Minus - Literal 3)"
+        );
+        assert_eq!(
+            ast("8 / 4 / 2;"),
+            "Binary(Binary(Literal 8 Slash / Literal 4) Slash / Literal 2)"
+        );
+        assert_eq!(
+            ast("1 < 2 == 3 > 4;"),
+            "Binary(Binary(Literal 1 Less < Literal 2) EqualEqual == \
+             Binary(Literal 3 Greater > Literal 4))"
+        );
+        assert_eq!(
+            ast("a or b or c;"),
+            "Logical(Logical(Variable Identifier a Or or Variable Identifier b) \
+             Or or Variable Identifier c)"
+        );
+        assert_eq!(
+            ast("a and b and c;"),
+            "Logical(Logical(Variable Identifier a And and Variable Identifier b) \
+             And and Variable Identifier c)"
+        );
+    }
+
+    #[test]
+    fn exponent_is_right_associative_and_binds_tighter_than_unary() {
+        assert_eq!(
+            ast("2 ** 3 ** 2;"),
+            "Binary(Literal 2 StarStar ** Binary(Literal 3 StarStar ** Literal 2))"
+        );
+        assert_eq!(
+            ast("-2 ** 2;"),
+            "Unary (Minus - Binary(Literal 2 StarStar ** Literal 2))"
+        );
+        assert_eq!(
+            ast("-a ** -b;"),
+            "Unary (Minus - Binary(Variable Identifier a StarStar ** \
+             Unary (Minus - Variable Identifier b)))"
+        );
+    }
+
+    #[test]
+    fn mixed_operators_follow_their_precedence() {
+        assert_eq!(
+            ast("1 + 2 * 3 - 4 / 5 % 6;"),
+            "Binary(Binary(Literal 1 Plus + Binary(Literal 2 Star * Literal 3)) Minus - \
+             Binary(Binary(Literal 4 Slash / Literal 5) Percent % Literal 6))"
+        );
+        assert_eq!(
+            ast("a or b and c == d < e + f * -g ** h;"),
+            "Logical(Variable Identifier a Or or Logical(Variable Identifier b And and \
+             Binary(Variable Identifier c EqualEqual == Binary(Variable Identifier d Less < \
+             Binary(Variable Identifier e Plus + Binary(Variable Identifier f Star * \
+             Unary (Minus - Binary(Variable Identifier g StarStar ** Variable Identifier h))))))))"
+        );
+        assert_eq!(
+            ast("!a == b;"),
+            "Binary(Unary (Bang ! Variable Identifier a) EqualEqual == Variable Identifier b)"
+        );
+        assert_eq!(
+            ast("-(1 + 2) * 3;"),
+            "Binary(Unary (Minus - (group Binary(Literal 1 Plus + Literal 2))) Star * Literal 3)"
+        );
+    }
+
+    #[test]
+    fn assignment_and_conditional_nest_to_the_right() {
+        assert_eq!(
+            ast("x = y = 1 + 2;"),
+            "Assign Identifier x, Assign Identifier y, Binary(Literal 1 Plus + Literal 2)"
+        );
+        assert_eq!(
+            ast("a ? b : c ? d : e;"),
+            "Conditional(Variable Identifier a ? Variable Identifier b : \
+             Conditional(Variable Identifier c ? Variable Identifier d : Variable Identifier e))"
+        );
+    }
+
+    //Whether parsing `source` reported an error, on a stack as large as
+    //lox gives it
+    fn fails(source: String) -> bool {
+        std::thread::Builder::new()
+            .stack_size(128 * 1024 * 1024)
+            .spawn(move || {
+                let mut parser = Parser::new(Scanner::new(source).scan_tokens());
+                parser.parse().expect("parse recovers from errors");
+                parser.had_error()
+            })
+            .expect("the parser thread starts")
+            .join()
+            .expect("parsing does not panic")
+    }
+
+    #[test]
+    fn nesting_past_the_limit_is_a_parse_error() {
+        let parentheses = |depth: usize| format!("{}1{};", "(".repeat(depth), ")".repeat(depth));
+        assert!(!fails(parentheses(MAX_NESTING / 2)));
+        assert!(fails(parentheses(MAX_NESTING * 2)));
+        let sum = |terms: usize| format!("1{};", " + 1".repeat(terms));
+        assert!(!fails(sum(MAX_NESTING / 2)));
+        assert!(fails(sum(MAX_NESTING * 2)));
+        let calls = |depth: usize| format!("f{};", "()".repeat(depth));
+        assert!(!fails(calls(MAX_NESTING / 2)));
+        assert!(fails(calls(MAX_NESTING * 2)));
+        let blocks = |depth: usize| format!("{}{}", "{".repeat(depth), "}".repeat(depth));
+        assert!(!fails(blocks(MAX_NESTING / 2)));
+        assert!(fails(blocks(MAX_NESTING * 2)));
+    }
+}
@@ -0,0 +1,100 @@
+use crate::scanner::token_type::TokenType;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+
+//Ordered from loosest to tightest binding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Precedence {
+    None,
+    Assignment,
+    Or,
+    And,
+    Equality,
+    Comparison,
+    Term,
+    Factor,
+    Unary,
+    Exponent,
+    Primary,
+}
+
+impl Precedence {
+    pub fn next(self) -> Precedence {
+        match self {
+            Precedence::None => Precedence::Assignment,
+            Precedence::Assignment => Precedence::Or,
+            Precedence::Or => Precedence::And,
+            Precedence::And => Precedence::Equality,
+            Precedence::Equality => Precedence::Comparison,
+            Precedence::Comparison => Precedence::Term,
+            Precedence::Term => Precedence::Factor,
+            Precedence::Factor => Precedence::Unary,
+            Precedence::Unary => Precedence::Exponent,
+            Precedence::Exponent | Precedence::Primary => Precedence::Primary,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Associativity {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct InfixRule {
+    pub precedence: Precedence,
+    pub associativity: Associativity,
+}
+
+impl InfixRule {
+    fn left(precedence: Precedence) -> Self {
+        InfixRule {
+            precedence,
+            associativity: Associativity::Left,
+        }
+    }
+
+    fn right(precedence: Precedence) -> Self {
+        InfixRule {
+            precedence,
+            associativity: Associativity::Right,
+        }
+    }
+
+    /// Precedence the right operand is parsed at: one level up for left
+    /// associative operators, the same level for right associative ones.
+    pub fn right_operand_precedence(&self) -> Precedence {
+        match self.associativity {
+            Associativity::Left => self.precedence.next(),
+            Associativity::Right => self.precedence,
+        }
+    }
+}
+
+//Binary operators, adding one is a matter of adding a row here
+pub static INFIX_RULES: Lazy<HashMap<TokenType, InfixRule>> = Lazy::new(|| {
+    let mut m = HashMap::new();
+    m.insert(TokenType::Or, InfixRule::left(Precedence::Or));
+    m.insert(TokenType::And, InfixRule::left(Precedence::And));
+    m.insert(TokenType::EqualEqual, InfixRule::left(Precedence::Equality));
+    m.insert(TokenType::BangEqual, InfixRule::left(Precedence::Equality));
+    m.insert(TokenType::Greater, InfixRule::left(Precedence::Comparison));
+    m.insert(
+        TokenType::GreaterEqual,
+        InfixRule::left(Precedence::Comparison),
+    );
+    m.insert(TokenType::Less, InfixRule::left(Precedence::Comparison));
+    m.insert(
+        TokenType::LessEqual,
+        InfixRule::left(Precedence::Comparison),
+    );
+    m.insert(TokenType::Minus, InfixRule::left(Precedence::Term));
+    m.insert(TokenType::Plus, InfixRule::left(Precedence::Term));
+    m.insert(TokenType::Slash, InfixRule::left(Precedence::Factor));
+    m.insert(TokenType::Star, InfixRule::left(Precedence::Factor));
+    m.insert(TokenType::Percent, InfixRule::left(Precedence::Factor));
+    m.insert(TokenType::TildeSlash, InfixRule::left(Precedence::Factor));
+    m.insert(TokenType::StarStar, InfixRule::right(Precedence::Exponent));
+    m
+});
@@ -16,6 +16,62 @@ pub enum Statement {
         name: Token,
         initializer: Expression,
     },
+    Block {
+        statements: Vec<Statement>,
+    },
+    If {
+        condition: Expression,
+        then_branch: Box<Statement>,
+        else_branch: Option<Box<Statement>>,
+    },
+    //increment is the third clause of a C-style for, run after every
+    //iteration including the ones cut short by continue
+    While {
+        condition: Expression,
+        body: Box<Statement>,
+        increment: Option<Expression>,
+    },
+    ForIn {
+        name: Token,
+        iterable: Expression,
+        body: Box<Statement>,
+    },
+    Break {
+        keyword: Token,
+    },
+    Continue {
+        keyword: Token,
+    },
+    Throw {
+        keyword: Token,
+        value: Expression,
+    },
+    //tail is set when value is a call whose result is returned as is, or a
+    //?: with one in a branch, and no try surrounds it. The call can then
+    //take over the caller's frame
+    Return {
+        keyword: Token,
+        value: Option<Expression>,
+        tail: bool,
+    },
+    //At least one of catch and finally is present
+    Try {
+        body: Vec<Statement>,
+        catch: Option<(Token, Vec<Statement>)>,
+        finally: Option<Vec<Statement>>,
+    },
+    //import "path" as alias;
+    Import {
+        keyword: Token,
+        path: Token,
+        alias: Token,
+    },
+    //from "path" import a, b;
+    FromImport {
+        keyword: Token,
+        path: Token,
+        names: Vec<Token>,
+    },
 }
 
 impl fmt::Display for Statement {
@@ -24,6 +80,77 @@ impl fmt::Display for Statement {
             Statement::Expr { expression } => write!(f, "{}", expression),
             Statement::Print { expression } => write!(f, "{}", expression),
             Statement::Var { name, initializer } => write!(f, "{} {}", name, initializer),
+            Statement::Block { statements } => {
+                write!(f, "Block {{")?;
+                for statement in statements {
+                    write!(f, " {};", statement)?;
+                }
+                write!(f, " }}")
+            }
+            Statement::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => match else_branch {
+                Some(else_branch) => {
+                    write!(f, "If ({}) {} else {}", condition, then_branch, else_branch)
+                }
+                None => write!(f, "If ({}) {}", condition, then_branch),
+            },
+            Statement::While {
+                condition,
+                body,
+                increment,
+            } => match increment {
+                Some(increment) => write!(f, "While ({}; {}) {}", condition, increment, body),
+                None => write!(f, "While ({}) {}", condition, body),
+            },
+            Statement::ForIn {
+                name,
+                iterable,
+                body,
+            } => write!(f, "ForIn ({} in {}) {}", name, iterable, body),
+            Statement::Break { .. } => write!(f, "Break"),
+            Statement::Continue { .. } => write!(f, "Continue"),
+            Statement::Throw { value, .. } => write!(f, "Throw {}", value),
+            Statement::Return { value, tail, .. } => match value {
+                Some(value) if *tail => write!(f, "Return tail {}", value),
+                Some(value) => write!(f, "Return {}", value),
+                None => write!(f, "Return"),
+            },
+            Statement::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                write!(
+                    f,
+                    "Try {}",
+                    Statement::Block {
+                        statements: body.clone()
+                    }
+                )?;
+                if let Some((name, handler)) = catch {
+                    let handler = Statement::Block {
+                        statements: handler.clone(),
+                    };
+                    write!(f, " catch ({}) {}", name.lexame, handler)?;
+                }
+                if let Some(finally) = finally {
+                    let finally = Statement::Block {
+                        statements: finally.clone(),
+                    };
+                    write!(f, " finally {}", finally)?;
+                }
+                Ok(())
+            }
+            Statement::Import { path, alias, .. } => {
+                write!(f, "Import {} as {}", path.lexame, alias.lexame)
+            }
+            Statement::FromImport { path, names, .. } => {
+                let names: Vec<&str> = names.iter().map(|name| name.lexame.as_str()).collect();
+                write!(f, "FromImport {} ({})", path.lexame, names.join(", "))
+            }
         }
     }
 }
@@ -31,16 +158,54 @@ impl fmt::Display for Statement {
 impl Statement {
     pub fn accept<R>(&self, visitor: &mut dyn VisitorStatement<R>) -> R {
         match self {
-            Statement::Expr { expression } => visitor.visit(&Statement::Expr {
-                expression: expression.clone(),
-            }),
-            Statement::Print { expression } => visitor.visit(&Statement::Print {
-                expression: expression.clone(),
-            }),
-            Statement::Var { name, initializer } => visitor.visit(&Statement::Var {
-                name: name.clone(),
-                initializer: initializer.clone(),
-            }),
+            Statement::Expr { expression } => visitor.visit_expr(expression),
+            Statement::Print { expression } => visitor.visit_print(expression),
+            Statement::Var { name, initializer } => visitor.visit_var(name, initializer),
+            Statement::Block { statements } => visitor.visit_block(statements),
+            Statement::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => visitor.visit_if(condition, then_branch, else_branch.as_deref()),
+            Statement::While {
+                condition,
+                body,
+                increment,
+            } => visitor.visit_while(condition, body, increment.as_ref()),
+            Statement::ForIn {
+                name,
+                iterable,
+                body,
+            } => visitor.visit_for_in(name, iterable, body),
+            Statement::Break { keyword } => visitor.visit_break(keyword),
+            Statement::Continue { keyword } => visitor.visit_continue(keyword),
+            Statement::Throw { keyword, value } => visitor.visit_throw(keyword, value),
+            Statement::Return {
+                keyword,
+                value,
+                tail,
+            } => visitor.visit_return(keyword, value.as_ref(), *tail),
+            Statement::Try {
+                body,
+                catch,
+                finally,
+            } => visitor.visit_try(
+                body,
+                catch
+                    .as_ref()
+                    .map(|(name, handler)| (name, handler.as_slice())),
+                finally.as_deref(),
+            ),
+            Statement::Import {
+                keyword,
+                path,
+                alias,
+            } => visitor.visit_import(keyword, path, alias),
+            Statement::FromImport {
+                keyword,
+                path,
+                names,
+            } => visitor.visit_from_import(keyword, path, names),
         }
     }
 }
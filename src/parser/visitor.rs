@@ -1,9 +1,85 @@
-use super::{expression::Expression, statement::Statement};
+use std::rc::Rc;
 
+use crate::{scanner::token::Token, utils::literal_value::LiteralValue};
+
+use super::{
+    expression::{Expression, Parameter},
+    statement::Statement,
+};
+
+//One method per variant, handed the variant's fields by reference so
+//visiting never copies the tree
 pub trait VisitorExpression<R> {
-    fn visit(&mut self, expression: &Expression) -> R;
+    fn visit_unary(&mut self, operator: &Token, right: &Expression) -> R;
+    fn visit_literal(&mut self, value: &LiteralValue) -> R;
+    fn visit_binary(&mut self, left: &Expression, operator: &Token, right: &Expression) -> R;
+    fn visit_grouping(&mut self, expression: &Expression) -> R;
+    fn visit_assign(&mut self, name: &Token, value: &Expression) -> R;
+    fn visit_variable(&mut self, name: &Token) -> R;
+    fn visit_logical(&mut self, left: &Expression, operator: &Token, right: &Expression) -> R;
+    fn visit_conditional(
+        &mut self,
+        condition: &Expression,
+        then_branch: &Expression,
+        else_branch: &Expression,
+    ) -> R;
+    fn visit_sequence(&mut self, expressions: &[Expression]) -> R;
+    fn visit_list(&mut self, elements: &[Expression]) -> R;
+    fn visit_map(&mut self, brace: &Token, entries: &[(Expression, Expression)]) -> R;
+    fn visit_index(&mut self, object: &Expression, bracket: &Token, index: &Expression) -> R;
+    fn visit_set_index(
+        &mut self,
+        object: &Expression,
+        bracket: &Token,
+        index: &Expression,
+        value: &Expression,
+    ) -> R;
+    fn visit_call(
+        &mut self,
+        callee: &Expression,
+        paren: &Token,
+        arguments: &[Expression],
+        named: &[(Token, Expression)],
+    ) -> R;
+    fn visit_get(&mut self, object: &Expression, name: &Token) -> R;
+    fn visit_interpolation(&mut self, parts: &[Expression]) -> R;
+    fn visit_lambda(
+        &mut self,
+        keyword: &Token,
+        name: Option<&Token>,
+        params: &Rc<Vec<Parameter>>,
+        body: &Rc<Vec<Statement>>,
+    ) -> R;
 }
 
 pub trait VisitorStatement<R> {
-    fn visit(&mut self, expression: &Statement) -> R;
+    fn visit_expr(&mut self, expression: &Expression) -> R;
+    fn visit_print(&mut self, expression: &Expression) -> R;
+    fn visit_var(&mut self, name: &Token, initializer: &Expression) -> R;
+    fn visit_block(&mut self, statements: &[Statement]) -> R;
+    fn visit_if(
+        &mut self,
+        condition: &Expression,
+        then_branch: &Statement,
+        else_branch: Option<&Statement>,
+    ) -> R;
+    fn visit_while(
+        &mut self,
+        condition: &Expression,
+        body: &Statement,
+        increment: Option<&Expression>,
+    ) -> R;
+    fn visit_for_in(&mut self, name: &Token, iterable: &Expression, body: &Statement) -> R;
+    fn visit_break(&mut self, keyword: &Token) -> R;
+    fn visit_continue(&mut self, keyword: &Token) -> R;
+    fn visit_throw(&mut self, keyword: &Token, value: &Expression) -> R;
+    fn visit_return(&mut self, keyword: &Token, value: Option<&Expression>, tail: bool) -> R;
+    fn visit_try(
+        &mut self,
+        body: &[Statement],
+        catch: Option<(&Token, &[Statement])>,
+        finally: Option<&[Statement]>,
+    ) -> R;
+    fn visit_import(&mut self, keyword: &Token, path: &Token, alias: &Token) -> R;
+    fn visit_from_import(&mut self, keyword: &Token, path: &Token, names: &[Token]) -> R;
 }
@@ -1,3 +1,5 @@
+pub mod scan_error;
 pub mod scanner;
 pub mod token;
 pub mod token_type;
+pub mod trivia;
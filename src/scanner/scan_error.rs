@@ -0,0 +1,17 @@
+#[derive(Debug, Clone)]
+pub struct ScanError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for ScanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[line {}] Error: {}", self.line, self.message)
+    }
+}
+
+impl ScanError {
+    pub fn new(message: String, line: usize) -> Self {
+        ScanError { line, message }
+    }
+}
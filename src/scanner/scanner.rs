@@ -1,51 +1,89 @@
-use super::{token::Token, token_type::TokenType};
-use crate::loxerror;
+use super::{
+    scan_error::ScanError,
+    token::Token,
+    token_type::TokenType,
+    trivia::{TokenTrivia, Trivia, TriviaKind},
+};
 use crate::utils::literal_value::LiteralValue;
 use crate::utils::reserved_words::KEYWORDS;
 pub struct Scanner {
-    source: String,
+    source: Vec<char>,
     tokens: Vec<Token>,
+    errors: Vec<ScanError>,
     start: usize,
     current: usize,
     line: usize,
+    keep_trivia: bool,
+    pending_trivia: Vec<Trivia>,
+    on_token_line: bool,
+    //One entry per open "${", counting the braces opened inside it so the
+    //matching "}" can hand control back to the string
+    interpolations: Vec<usize>,
 }
 
 impl Scanner {
     pub fn new(source: String) -> Scanner {
         let tokens = Vec::new();
+        let errors = Vec::new();
         let start = 0;
         let current = 0;
         let line = 1;
         Scanner {
-            source,
+            source: source.chars().collect(),
             tokens,
+            errors,
             start,
             current,
             line,
+            keep_trivia: false,
+            pending_trivia: Vec::new(),
+            on_token_line: false,
+            interpolations: Vec::new(),
         }
     }
 
+    /// Lossless mode: whitespace, newlines and comments are attached to the
+    /// tokens instead of being dropped, so concatenating `Token::full_text`
+    /// over the whole stream gives back the source unchanged.
+    ///
+    /// Trivia following a token on the same line is its trailing trivia,
+    /// everything else leads the next token (the `Eof` token included).
+    pub fn with_trivia(source: String) -> Scanner {
+        let mut scanner = Scanner::new(source);
+        scanner.keep_trivia = true;
+        scanner
+    }
+
     pub fn scan_tokens(&mut self) -> Vec<Token> {
         while !self.is_at_end() {
             self.start = self.current;
             self.scan_token();
         }
-        self.tokens.push(Token {
-            token_type: TokenType::Eof,
-            lexame: String::new(),
-            line: self.line,
-            literal: LiteralValue::Nil,
-        });
+        self.start = self.current;
+        if !self.interpolations.is_empty() {
+            self.interpolations.clear();
+            self.add_error("Unterminated string interpolation".to_string());
+        }
+        self.add_token(TokenType::Eof, LiteralValue::Nil);
         self.tokens.to_vec()
     }
 
+    /// Lexical errors found by the last `scan_tokens` call, in source order.
+    /// Each one also has a matching `TokenType::Error` token in the stream.
+    pub fn errors(&self) -> Vec<ScanError> {
+        self.errors.to_vec()
+    }
+
+    pub fn had_error(&self) -> bool {
+        !self.errors.is_empty()
+    }
+
     fn is_at_end(&self) -> bool {
-        self.current >= self.source.len() - 1
+        self.current >= self.source.len()
     }
 
     fn advance(&mut self) -> char {
-        let char_vec: Vec<char> = self.source.chars().collect();
-        let current_char = char_vec[self.current];
+        let current_char = self.source[self.current];
         if !self.is_at_end() {
             self.current += 1;
         }
@@ -53,18 +91,50 @@ impl Scanner {
         return current_char;
     }
 
+    fn text(&self, from: usize, to: usize) -> String {
+        self.source[from..to].iter().collect()
+    }
+
     fn add_token(&mut self, token_type: TokenType, literal: LiteralValue) {
-        let text: String = self.source[self.start..self.current].to_string();
-        self.tokens
-            .push(Token::new(token_type, text, self.line, literal))
+        let text: String = self.text(self.start, self.current);
+        let mut token = Token::new(token_type, text, self.line, literal);
+        if !self.pending_trivia.is_empty() {
+            token.trivia = Some(Box::new(TokenTrivia {
+                leading: std::mem::take(&mut self.pending_trivia),
+                trailing: Vec::new(),
+            }));
+        }
+        self.tokens.push(token);
+        self.on_token_line = true;
+    }
+
+    fn add_error(&mut self, message: String) {
+        self.add_token(TokenType::Error, LiteralValue::String(message.clone()));
+        self.errors.push(ScanError::new(message, self.line));
     }
 
-    fn match_token(&mut self, expected: String) -> bool {
+    fn add_trivia(&mut self, kind: TriviaKind) {
+        if !self.keep_trivia {
+            return;
+        }
+        let trivia = Trivia::new(kind, self.text(self.start, self.current));
+        match (kind, self.on_token_line, self.tokens.last_mut()) {
+            (TriviaKind::Newline, _, _) | (_, false, _) | (_, true, None) => {
+                self.pending_trivia.push(trivia)
+            }
+            (_, true, Some(token)) => token
+                .trivia
+                .get_or_insert_with(Box::default)
+                .trailing
+                .push(trivia),
+        }
+    }
+
+    fn match_token(&mut self, expected: char) -> bool {
         if self.is_at_end() {
             return false;
         }
-        let char_vec: Vec<char> = self.source.chars().collect();
-        if char_vec[self.current].to_string() != expected {
+        if self.source[self.current] != expected {
             return false;
         }
         self.current += 1;
@@ -77,37 +147,47 @@ impl Scanner {
             //Small lexemes
             '(' => self.add_token(TokenType::LeftParen, LiteralValue::None),
             ')' => self.add_token(TokenType::RightParen, LiteralValue::None),
-            '{' => self.add_token(TokenType::RightBrace, LiteralValue::None),
-            '}' => self.add_token(TokenType::LeftBrace, LiteralValue::None),
+            '{' => self.scan_left_brace_token(),
+            '}' => self.scan_right_brace_token(),
+            '[' => self.add_token(TokenType::LeftBracket, LiteralValue::None),
+            ']' => self.add_token(TokenType::RightBracket, LiteralValue::None),
             ',' => self.add_token(TokenType::Comma, LiteralValue::None),
-            '.' => self.add_token(TokenType::Dot, LiteralValue::None),
+            '.' => self.scan_dot_token(),
             '-' => self.add_token(TokenType::Minus, LiteralValue::None),
             '+' => self.add_token(TokenType::Plus, LiteralValue::None),
             ';' => self.add_token(TokenType::Semicolon, LiteralValue::None),
-            '*' => self.add_token(TokenType::Star, LiteralValue::None),
+            '*' => self.scan_star_star_token(),
+            '%' => self.add_token(TokenType::Percent, LiteralValue::None),
+            '?' => self.add_token(TokenType::Question, LiteralValue::None),
+            ':' => self.add_token(TokenType::Colon, LiteralValue::None),
+            '~' => self.scan_tilde_slash_token(),
             '=' => self.scan_equal_equal_token(),
             '!' => self.scan_bang_equal_token(),
             '<' => self.scan_less_equal_token(),
             '>' => self.scan_greater_equal_token(),
             '/' => self.scan_slash_token(),
-            ' ' | '\r' | '\t' => (),
-            '\n' => self.line += 1,
+            ' ' | '\r' | '\t' => self.whitespace(),
+            '\n' => {
+                self.line += 1;
+                self.add_trivia(TriviaKind::Newline);
+                self.on_token_line = false;
+            }
             '"' => self.string(),
             '0'..='9' => self.number(),
             'a'..='z' | 'A'..='Z' | '_' => self.identifier(),
-            _ => loxerror(self.line, "Unexpected Character".to_string()),
+            _ => self.add_error(format!("Unexpected character '{}'", character)),
         }
     }
 
     fn is_alpha_numeric(&self, c: char) -> bool {
-        return ('a'..='z').contains(&c) || ('A'..='Z').contains(&c) || ('0'..='9').contains(&c);
+        return c.is_ascii_alphanumeric() || c == '_';
     }
 
     fn identifier(&mut self) {
         while self.is_alpha_numeric(self.peek()) {
             self.advance();
         }
-        let text = self.source[self.start..self.current].to_string();
+        let text = self.text(self.start, self.current);
         let token_type = &KEYWORDS
             .get(&text.as_str())
             .copied()
@@ -116,88 +196,203 @@ impl Scanner {
         self.add_token(token_type.to_owned(), LiteralValue::None)
     }
 
+    fn whitespace(&mut self) {
+        while matches!(self.peek(), ' ' | '\r' | '\t') {
+            self.advance();
+        }
+        self.add_trivia(TriviaKind::Whitespace);
+    }
+
     fn scan_slash_token(&mut self) {
-        if self.match_token('/'.to_string()) {
+        if self.match_token('/') {
             while self.peek() != '\n' && !self.is_at_end() {
                 self.advance();
             }
+            self.add_trivia(TriviaKind::LineComment);
+        } else if self.match_token('*') {
+            self.block_comment();
         } else {
             self.add_token(TokenType::Slash, LiteralValue::None);
         }
     }
 
+    fn block_comment(&mut self) {
+        let start_line = self.line;
+        while !self.is_at_end() && (self.peek() != '*' || self.peek_next() != '/') {
+            if self.peek() == '\n' {
+                self.line += 1;
+            }
+            self.advance();
+        }
+        if self.is_at_end() {
+            self.add_error("Unterminated block comment".to_string());
+            return;
+        }
+        self.advance();
+        self.advance();
+        self.add_trivia(TriviaKind::BlockComment);
+        if self.line != start_line {
+            self.on_token_line = false;
+        }
+    }
+
     fn number(&mut self) {
-        while ('0'..='9').contains(&self.peek()) {
+        while self.peek().is_ascii_digit() {
             self.advance();
         }
 
-        while self.peek() == '.' && ('0'..='9').contains(&self.peek_next()) {
+        while self.peek() == '.' && self.peek_next().is_ascii_digit() {
             self.advance();
-            while ('0'..='9').contains(&self.peek()) {
+            while self.peek().is_ascii_digit() {
                 self.advance();
             }
         }
         self.add_token(
             TokenType::Number,
-            LiteralValue::Float(match self.source[self.start..self.current].parse() {
-                Ok(v) => v,
-                Err(_) => 0.666,
-            }),
+            LiteralValue::Float(self.text(self.start, self.current).parse().unwrap_or(0.666)),
         )
     }
 
+    /// Scans string text up to the closing quote, starting just after the
+    /// opening quote or after the "}" ending an interpolated expression.
+    ///
+    /// "a ${x} b ${y} c" becomes Interpolation("a ") x Interpolation(" b ")
+    /// y String(" c"), each segment keeping its delimiters in the lexeme.
     fn string(&mut self) {
         while self.peek() != '"' && !self.is_at_end() {
+            if self.peek() == '$' && self.peek_next() == '{' {
+                self.advance();
+                self.advance();
+                let value: String = self.text(self.start + 1, self.current - 2);
+                self.add_token(TokenType::Interpolation, LiteralValue::String(value));
+                self.interpolations.push(0);
+                return;
+            }
             if self.peek() == '\n' {
                 self.line += 1;
             }
             self.advance();
         }
         if self.is_at_end() {
-            loxerror(self.line, "rlox:: Undeterminated String".to_string());
+            self.add_error("Unterminated string".to_string());
             return;
         }
         self.advance();
-        let value: String = self.source[(self.start + 1)..(self.current - 1)].to_string();
+        let value: String = self.text(self.start + 1, self.current - 1);
         self.add_token(TokenType::String, LiteralValue::String(value))
     }
 
+    fn scan_left_brace_token(&mut self) {
+        if let Some(depth) = self.interpolations.last_mut() {
+            *depth += 1;
+        }
+        self.add_token(TokenType::LeftBrace, LiteralValue::None)
+    }
+    fn scan_right_brace_token(&mut self) {
+        match self.interpolations.last_mut() {
+            Some(0) => {
+                self.interpolations.pop();
+                self.string();
+            }
+            Some(depth) => {
+                *depth -= 1;
+                self.add_token(TokenType::RightBrace, LiteralValue::None)
+            }
+            None => self.add_token(TokenType::RightBrace, LiteralValue::None),
+        }
+    }
+    fn scan_dot_token(&mut self) {
+        match self.peek() == '.' && self.peek_next() == '.' {
+            true => {
+                self.advance();
+                self.advance();
+                self.add_token(TokenType::Ellipsis, LiteralValue::None)
+            }
+            false => self.add_token(TokenType::Dot, LiteralValue::None),
+        }
+    }
+    fn scan_star_star_token(&mut self) {
+        match self.match_token('*') {
+            true => self.add_token(TokenType::StarStar, LiteralValue::None),
+            false => self.add_token(TokenType::Star, LiteralValue::None),
+        }
+    }
+    fn scan_tilde_slash_token(&mut self) {
+        match self.match_token('/') {
+            true => self.add_token(TokenType::TildeSlash, LiteralValue::None),
+            false => self.add_error("Unexpected character '~'".to_string()),
+        }
+    }
     fn scan_equal_equal_token(&mut self) {
-        match self.match_token("=".to_string()) {
+        if self.match_token('>') {
+            return self.add_token(TokenType::Arrow, LiteralValue::None);
+        }
+        match self.match_token('=') {
             true => self.add_token(TokenType::EqualEqual, LiteralValue::None),
             false => self.add_token(TokenType::Equal, LiteralValue::None),
         }
     }
     fn scan_less_equal_token(&mut self) {
-        match self.match_token("=".to_string()) {
+        match self.match_token('=') {
             true => self.add_token(TokenType::LessEqual, LiteralValue::None),
             false => self.add_token(TokenType::Less, LiteralValue::None),
         }
     }
     fn scan_bang_equal_token(&mut self) {
-        match self.match_token("=".to_string()) {
+        match self.match_token('=') {
             true => self.add_token(TokenType::BangEqual, LiteralValue::None),
             false => self.add_token(TokenType::Bang, LiteralValue::None),
         }
     }
     fn scan_greater_equal_token(&mut self) {
-        match self.match_token("=".to_string()) {
+        match self.match_token('=') {
             true => self.add_token(TokenType::GreaterEqual, LiteralValue::None),
             false => self.add_token(TokenType::Greater, LiteralValue::None),
         }
     }
     fn peek(&self) -> char {
-        let char_vec: Vec<char> = self.source.chars().collect();
         if self.is_at_end() {
             return '\0';
         }
-        return char_vec[self.current];
+        return self.source[self.current];
     }
     fn peek_next(&self) -> char {
-        let char_vec: Vec<char> = self.source.chars().collect();
         if self.current + 1 >= self.source.len() {
             return '\0';
         }
-        return char_vec[self.current + 1];
+        return self.source[self.current + 1];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(source: &str) -> String {
+        let tokens = Scanner::with_trivia(source.to_string()).scan_tokens();
+        tokens.iter().map(Token::full_text).collect()
+    }
+
+    #[test]
+    fn trivia_gives_back_the_source() {
+        let sources = [
+            "",
+            "   \n\n",
+            "print 1;",
+            "var a = 1;   // trailing comment\n\n  // own line\nprint a;\n",
+            "/* block\n   over lines */ fun f(x) {\r\n\treturn x ** 2; }\n",
+            "print \"multi\nline\" + \"${1 + 2} and ${\"${nested}\"}\";  ",
+            "var m = {\"a\": [1, 2.5, nil]}; // no newline at the end",
+            "print @;\n\"unterminated",
+        ];
+        for source in sources {
+            assert_eq!(round_trip(source), source);
+        }
+    }
+
+    #[test]
+    fn plain_scanner_keeps_no_trivia() {
+        let tokens = Scanner::new("var a = 1; // comment\n".to_string()).scan_tokens();
+        assert!(tokens.iter().all(|token| token.trivia.is_none()));
     }
 }
@@ -2,7 +2,7 @@ use core::fmt;
 
 use crate::utils::literal_value::LiteralValue;
 
-use super::token_type::TokenType;
+use super::{token_type::TokenType, trivia::TokenTrivia};
 
 #[derive(Debug, Clone)]
 pub struct Token {
@@ -10,6 +10,9 @@ pub struct Token {
     pub lexame: String,
     pub line: usize,
     pub literal: LiteralValue,
+    //Only filled by a scanner built with Scanner::with_trivia. Boxed, the
+    //parser and interpreter copy tokens into every error they raise
+    pub trivia: Option<Box<TokenTrivia>>,
 }
 
 impl Token {
@@ -19,8 +22,26 @@ impl Token {
             lexame,
             line,
             literal,
+            trivia: None,
         }
     }
+
+    /// The token exactly as written in the source, trivia included.
+    pub fn full_text(&self) -> String {
+        let (leading, trailing) = match &self.trivia {
+            Some(trivia) => (&trivia.leading[..], &trivia.trailing[..]),
+            None => (&[][..], &[][..]),
+        };
+        let mut text = String::new();
+        for trivia in leading {
+            text.push_str(&trivia.text);
+        }
+        text.push_str(&self.lexame);
+        for trivia in trailing {
+            text.push_str(&trivia.text);
+        }
+        return text;
+    }
 }
 
 impl fmt::Display for Token {
@@ -1,11 +1,13 @@
 use std::fmt;
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum TokenType {
     //Single char tokens
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
     Minus,
@@ -13,6 +15,9 @@ pub enum TokenType {
     Semicolon,
     Slash,
     Star,
+    Percent,
+    Question,
+    Colon,
 
     //One or two char tokens
     Bang,
@@ -23,20 +28,34 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    StarStar,
+    TildeSlash,
 
     //Literals
     Identifier,
     String,
+    //String text followed by an interpolated "${" expression
+    Interpolation,
     Number,
 
     //Keywords
     And,
+    Break,
     Class,
+    Continue,
+    Import,
+    Arrow,
+    Ellipsis,
+    Throw,
+    Try,
+    Catch,
+    Finally,
     Else,
     False,
     Fun,
     For,
     If,
+    In,
     Nil,
     Or,
     Print,
@@ -47,9 +66,80 @@ pub enum TokenType {
     Var,
     While,
 
+    //Lexical error, the literal carries the message
+    Error,
+
     Eof,
 }
 
+//Every token type, in declaration order so `as u8` indexes it
+const TOKEN_TYPES: [TokenType; 58] = [
+    TokenType::LeftParen,
+    TokenType::RightParen,
+    TokenType::LeftBrace,
+    TokenType::RightBrace,
+    TokenType::LeftBracket,
+    TokenType::RightBracket,
+    TokenType::Comma,
+    TokenType::Dot,
+    TokenType::Minus,
+    TokenType::Plus,
+    TokenType::Semicolon,
+    TokenType::Slash,
+    TokenType::Star,
+    TokenType::Percent,
+    TokenType::Question,
+    TokenType::Colon,
+    TokenType::Bang,
+    TokenType::BangEqual,
+    TokenType::Equal,
+    TokenType::EqualEqual,
+    TokenType::Greater,
+    TokenType::GreaterEqual,
+    TokenType::Less,
+    TokenType::LessEqual,
+    TokenType::StarStar,
+    TokenType::TildeSlash,
+    TokenType::Identifier,
+    TokenType::String,
+    TokenType::Interpolation,
+    TokenType::Number,
+    TokenType::And,
+    TokenType::Break,
+    TokenType::Class,
+    TokenType::Continue,
+    TokenType::Import,
+    TokenType::Arrow,
+    TokenType::Ellipsis,
+    TokenType::Throw,
+    TokenType::Try,
+    TokenType::Catch,
+    TokenType::Finally,
+    TokenType::Else,
+    TokenType::False,
+    TokenType::Fun,
+    TokenType::For,
+    TokenType::If,
+    TokenType::In,
+    TokenType::Nil,
+    TokenType::Or,
+    TokenType::Print,
+    TokenType::Return,
+    TokenType::Super,
+    TokenType::This,
+    TokenType::True,
+    TokenType::Var,
+    TokenType::While,
+    TokenType::Error,
+    TokenType::Eof,
+];
+
+impl TokenType {
+    pub fn from_byte(byte: u8) -> Option<TokenType> {
+        TOKEN_TYPES.get(byte as usize).copied()
+    }
+}
+
 impl fmt::Display for TokenType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let token_str = match self {
@@ -57,6 +147,8 @@ impl fmt::Display for TokenType {
             TokenType::RightParen => "RightParen",
             TokenType::LeftBrace => "LeftBrace",
             TokenType::RightBrace => "RightBrace",
+            TokenType::LeftBracket => "LeftBracket",
+            TokenType::RightBracket => "RightBracket",
             TokenType::Comma => "Comma",
             TokenType::Dot => "Dot",
             TokenType::Minus => "Minus",
@@ -64,6 +156,9 @@ impl fmt::Display for TokenType {
             TokenType::Semicolon => "Semicolon",
             TokenType::Slash => "Slash",
             TokenType::Star => "Star",
+            TokenType::Percent => "Percent",
+            TokenType::Question => "Question",
+            TokenType::Colon => "Colon",
 
             TokenType::Bang => "Bang",
             TokenType::BangEqual => "BangEqual",
@@ -73,18 +168,31 @@ impl fmt::Display for TokenType {
             TokenType::GreaterEqual => "GreaterEqual",
             TokenType::Less => "Less",
             TokenType::LessEqual => "LessEqual",
+            TokenType::StarStar => "StarStar",
+            TokenType::TildeSlash => "TildeSlash",
 
             TokenType::Identifier => "Identifier",
             TokenType::String => "String",
+            TokenType::Interpolation => "Interpolation",
             TokenType::Number => "Number",
 
             TokenType::And => "And",
+            TokenType::Break => "Break",
             TokenType::Class => "Class",
+            TokenType::Continue => "Continue",
+            TokenType::Import => "Import",
+            TokenType::Arrow => "Arrow",
+            TokenType::Ellipsis => "Ellipsis",
+            TokenType::Throw => "Throw",
+            TokenType::Try => "Try",
+            TokenType::Catch => "Catch",
+            TokenType::Finally => "Finally",
             TokenType::Else => "Else",
             TokenType::False => "False",
             TokenType::Fun => "Fun",
             TokenType::For => "For",
             TokenType::If => "If",
+            TokenType::In => "In",
             TokenType::Nil => "Nil",
             TokenType::Or => "Or",
             TokenType::Print => "Print",
@@ -95,6 +203,8 @@ impl fmt::Display for TokenType {
             TokenType::Var => "Var",
             TokenType::While => "While",
 
+            TokenType::Error => "Error",
+
             TokenType::Eof => "Eof",
         };
 
@@ -0,0 +1,36 @@
+use core::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriviaKind {
+    Whitespace,
+    Newline,
+    LineComment,
+    BlockComment,
+}
+
+/// Source text the parser does not care about, kept by the lossless scanner
+/// so tools can rebuild the original file from the token stream.
+#[derive(Debug, Clone)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+}
+
+/// Trivia around one token: what precedes it and what follows it on its line.
+#[derive(Debug, Clone, Default)]
+pub struct TokenTrivia {
+    pub leading: Vec<Trivia>,
+    pub trailing: Vec<Trivia>,
+}
+
+impl Trivia {
+    pub fn new(kind: TriviaKind, text: String) -> Self {
+        Self { kind, text }
+    }
+}
+
+impl fmt::Display for Trivia {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} {:?}", self.kind, self.text)
+    }
+}
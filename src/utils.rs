@@ -1,2 +1,8 @@
 pub mod literal_value;
+pub mod lox_error;
+pub mod lox_map;
+pub mod lox_range;
+pub mod namespace;
+pub mod native_function;
+pub mod random;
 pub mod reserved_words;
@@ -1,17 +1,208 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    interpreter::{iterator::LoxIterator, lox_function::LoxFunction},
+    vm::function::Closure,
+};
+
+use super::{
+    lox_error::LoxError, lox_map::LoxMap, lox_range::LoxRange, namespace::Namespace,
+    native_function::NativeFunction,
+};
+
 #[derive(Debug, Clone)]
 pub enum LiteralValue {
     Float(f64),
     Boolean(bool),
     String(String),
+    //Shared and mutable: every copy of a list value sees pushes and index sets
+    List(Rc<RefCell<Vec<LiteralValue>>>),
+    Map(Rc<RefCell<LoxMap>>),
+    Range(LoxRange),
+    Native(NativeFunction),
+    //A method looked up on a value, calling it passes `receiver` first
+    BoundMethod {
+        receiver: Box<LiteralValue>,
+        method: NativeFunction,
+    },
+    Function(Rc<LoxFunction>),
+    //A function compiled for the bytecode VM
+    Closure(Rc<Closure>),
+    //The loop state of a for-in running on the VM, never seen by scripts
+    Iterator(Rc<RefCell<LoxIterator>>),
+    Namespace(Rc<Namespace>),
+    Error(Rc<LoxError>),
     Nil,
     None,
 }
+
+impl LiteralValue {
+    pub fn list(elements: Vec<LiteralValue>) -> LiteralValue {
+        LiteralValue::List(Rc::new(RefCell::new(elements)))
+    }
+
+    pub fn map(entries: LoxMap) -> LiteralValue {
+        LiteralValue::Map(Rc::new(RefCell::new(entries)))
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            LiteralValue::Float(_) => "number",
+            LiteralValue::Boolean(_) => "boolean",
+            LiteralValue::String(_) => "string",
+            LiteralValue::List(_) => "list",
+            LiteralValue::Map(_) => "map",
+            LiteralValue::Range(_) => "range",
+            LiteralValue::Native(_)
+            | LiteralValue::BoundMethod { .. }
+            | LiteralValue::Function(_)
+            | LiteralValue::Closure(_) => "function",
+            LiteralValue::Iterator(_) => "iterator",
+            LiteralValue::Namespace(_) => "namespace",
+            LiteralValue::Error(_) => "error",
+            LiteralValue::Nil => "nil",
+            LiteralValue::None => "none",
+        }
+    }
+
+    //How a value looks inside a collection, where strings keep their quotes.
+    //`visiting` holds the collections being printed around it
+    fn fmt_nested(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        visiting: &mut Vec<*const ()>,
+    ) -> std::fmt::Result {
+        match self {
+            LiteralValue::String(value) => write!(f, "{:?}", value),
+            LiteralValue::List(elements) => Self::fmt_list(elements, f, visiting),
+            LiteralValue::Map(entries) => Self::fmt_map(entries, f, visiting),
+            _ => write!(f, "{}", self),
+        }
+    }
+
+    //A list inside itself prints as [...] instead of recursing forever
+    fn fmt_list(
+        elements: &Rc<RefCell<Vec<LiteralValue>>>,
+        f: &mut std::fmt::Formatter<'_>,
+        visiting: &mut Vec<*const ()>,
+    ) -> std::fmt::Result {
+        let pointer = Rc::as_ptr(elements) as *const ();
+        if visiting.contains(&pointer) {
+            return write!(f, "[...]");
+        }
+        visiting.push(pointer);
+        write!(f, "[")?;
+        for (index, element) in elements.borrow().iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            element.fmt_nested(f, visiting)?;
+        }
+        visiting.pop();
+        write!(f, "]")
+    }
+
+    //A map inside itself prints as {...}
+    fn fmt_map(
+        entries: &Rc<RefCell<LoxMap>>,
+        f: &mut std::fmt::Formatter<'_>,
+        visiting: &mut Vec<*const ()>,
+    ) -> std::fmt::Result {
+        let pointer = Rc::as_ptr(entries) as *const ();
+        if visiting.contains(&pointer) {
+            return write!(f, "{{...}}");
+        }
+        visiting.push(pointer);
+        write!(f, "{{")?;
+        for (index, (key, value)) in entries.borrow().iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            key.to_value().fmt_nested(f, visiting)?;
+            write!(f, ": ")?;
+            value.fmt_nested(f, visiting)?;
+        }
+        visiting.pop();
+        write!(f, "}}")
+    }
+
+    //Equality over the pairs of collections `comparing` holds being compared
+    //already. Meeting one of them again means both sides are cyclic, where
+    //only being the same collection makes them equal
+    fn equals(&self, other: &Self, comparing: &mut Vec<(*const (), *const ())>) -> bool {
+        match (self, other) {
+            (LiteralValue::List(lv), LiteralValue::List(rv)) => {
+                if Rc::ptr_eq(lv, rv) {
+                    return true;
+                }
+                let pair = (Rc::as_ptr(lv) as *const (), Rc::as_ptr(rv) as *const ());
+                if comparing.contains(&pair) {
+                    return false;
+                }
+                comparing.push(pair);
+                let (left, right) = (lv.borrow(), rv.borrow());
+                let equal = left.len() == right.len()
+                    && left
+                        .iter()
+                        .zip(right.iter())
+                        .all(|(left, right)| left.equals(right, comparing));
+                comparing.pop();
+                equal
+            }
+            (LiteralValue::Map(lv), LiteralValue::Map(rv)) => {
+                if Rc::ptr_eq(lv, rv) {
+                    return true;
+                }
+                let pair = (Rc::as_ptr(lv) as *const (), Rc::as_ptr(rv) as *const ());
+                if comparing.contains(&pair) {
+                    return false;
+                }
+                comparing.push(pair);
+                let equal = lv
+                    .borrow()
+                    .equal_by(&rv.borrow(), |left, right| left.equals(right, comparing));
+                comparing.pop();
+                equal
+            }
+            (
+                LiteralValue::BoundMethod {
+                    receiver: lr,
+                    method: lm,
+                },
+                LiteralValue::BoundMethod {
+                    receiver: rr,
+                    method: rm,
+                },
+            ) => lm.name == rm.name && lr.equals(rr, comparing),
+            _ => self == other,
+        }
+    }
+}
+
 impl std::fmt::Display for LiteralValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             LiteralValue::Float(value) => write!(f, "{}", value),
             LiteralValue::Boolean(value) => write!(f, "{}", value),
             LiteralValue::String(value) => write!(f, "{}", value),
+            LiteralValue::List(elements) => Self::fmt_list(elements, f, &mut Vec::new()),
+            LiteralValue::Map(entries) => Self::fmt_map(entries, f, &mut Vec::new()),
+            LiteralValue::Range(range) => write!(f, "{}", range),
+            LiteralValue::Native(native) => write!(f, "<native fn {}>", native.name),
+            LiteralValue::BoundMethod { method, .. } => {
+                write!(f, "<native method {}>", method.name)
+            }
+            LiteralValue::Function(function) => match &function.name {
+                Some(name) => write!(f, "<fn {}>", name),
+                None => write!(f, "<lambda>"),
+            },
+            LiteralValue::Closure(closure) => match &closure.function.name {
+                Some(name) => write!(f, "<fn {}>", name),
+                None => write!(f, "<lambda>"),
+            },
+            LiteralValue::Iterator(_) => write!(f, "<iterator>"),
+            LiteralValue::Namespace(namespace) => write!(f, "<namespace {}>", namespace.name),
+            LiteralValue::Error(error) => write!(f, "{}", error),
             LiteralValue::Nil => write!(f, "Nil"),
             LiteralValue::None => write!(f, "None"),
             // Add display logic for other variants if necessary
@@ -24,9 +215,83 @@ impl PartialEq for LiteralValue {
             (LiteralValue::Float(lv), LiteralValue::Float(rv)) => lv == rv,
             (LiteralValue::Boolean(lv), LiteralValue::Boolean(rv)) => lv == rv,
             (LiteralValue::String(lv), LiteralValue::String(rv)) => lv == rv,
+            (LiteralValue::List(_), LiteralValue::List(_))
+            | (LiteralValue::Map(_), LiteralValue::Map(_))
+            | (LiteralValue::BoundMethod { .. }, LiteralValue::BoundMethod { .. }) => {
+                self.equals(other, &mut Vec::new())
+            }
+            (LiteralValue::Range(lv), LiteralValue::Range(rv)) => lv == rv,
+            (LiteralValue::Native(lv), LiteralValue::Native(rv)) => lv.name == rv.name,
+            (LiteralValue::Function(lv), LiteralValue::Function(rv)) => Rc::ptr_eq(lv, rv),
+            (LiteralValue::Closure(lv), LiteralValue::Closure(rv)) => Rc::ptr_eq(lv, rv),
+            (LiteralValue::Iterator(lv), LiteralValue::Iterator(rv)) => Rc::ptr_eq(lv, rv),
+            (LiteralValue::Namespace(lv), LiteralValue::Namespace(rv)) => Rc::ptr_eq(lv, rv),
+            (LiteralValue::Error(lv), LiteralValue::Error(rv)) => Rc::ptr_eq(lv, rv),
             (LiteralValue::Nil, LiteralValue::Nil) => true,
             (LiteralValue::None, LiteralValue::None) => true,
             _ => false,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::lox_map::MapKey;
+
+    //A list holding `first` and then itself
+    fn self_containing(first: f64) -> LiteralValue {
+        let list = LiteralValue::list(vec![LiteralValue::Float(first)]);
+        if let LiteralValue::List(elements) = &list {
+            elements.borrow_mut().push(list.clone());
+        }
+        list
+    }
+
+    #[test]
+    fn cyclic_list_prints_the_cycle_as_ellipsis() {
+        let list = self_containing(1.0);
+        assert_eq!(list.to_string(), "[1, [...]]");
+        let outer = LiteralValue::list(vec![list.clone(), list]);
+        assert_eq!(outer.to_string(), "[[1, [...]], [1, [...]]]");
+    }
+
+    //A map holding `value` under "value" and itself under "self"
+    fn self_containing_map(value: f64) -> LiteralValue {
+        let map = LiteralValue::map(LoxMap::new());
+        if let LiteralValue::Map(entries) = &map {
+            let mut entries = entries.borrow_mut();
+            entries.insert(
+                MapKey::String("value".to_string()),
+                LiteralValue::Float(value),
+            );
+            entries.insert(MapKey::String("self".to_string()), map.clone());
+        }
+        map
+    }
+
+    #[test]
+    fn cyclic_map_prints_the_cycle_as_ellipsis() {
+        let map = self_containing_map(1.0);
+        assert_eq!(map.to_string(), "{\"value\": 1, \"self\": {...}}");
+        let list = LiteralValue::list(vec![map]);
+        assert_eq!(list.to_string(), "[{\"value\": 1, \"self\": {...}}]");
+    }
+
+    #[test]
+    fn cyclic_maps_compare_by_identity() {
+        let (a, b) = (self_containing_map(1.0), self_containing_map(1.0));
+        assert!(a == a.clone());
+        assert!(a != b);
+    }
+
+    #[test]
+    fn cyclic_lists_compare_by_identity() {
+        let (a, b) = (self_containing(1.0), self_containing(1.0));
+        assert!(a == a.clone());
+        assert!(a != b);
+        assert!(a != self_containing(2.0));
+        let c = LiteralValue::list(vec![LiteralValue::Float(1.0), a.clone()]);
+        assert!(a == c);
+    }
+}
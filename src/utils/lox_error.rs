@@ -0,0 +1,68 @@
+use std::fmt;
+
+use crate::scanner::token::Token;
+
+/// Categories of the runtime errors raised by the interpreter itself, what
+/// `e.kind` reads when one is caught.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorKind {
+    Type,
+    Name,
+    Attribute,
+    Index,
+    Key,
+    ZeroDivision,
+    Import,
+    //Calls nested deeper than the interpreter allows
+    Recursion,
+    //Failures reported by natives, and the default for error()
+    Generic,
+}
+
+impl ErrorKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorKind::Type => "TypeError",
+            ErrorKind::Name => "NameError",
+            ErrorKind::Attribute => "AttributeError",
+            ErrorKind::Index => "IndexError",
+            ErrorKind::Key => "KeyError",
+            ErrorKind::ZeroDivision => "ZeroDivisionError",
+            ErrorKind::Import => "ImportError",
+            ErrorKind::Recursion => "RecursionError",
+            ErrorKind::Generic => "Error",
+        }
+    }
+}
+
+/// An error object, the value `catch (e)` binds for builtin failures and
+/// what `error(message, kind)` builds. Fields read as `e.message`, `e.kind`
+/// and `e.line`.
+#[derive(Debug, Clone)]
+pub struct LoxError {
+    //Any string, builtin errors use the names of `ErrorKind`
+    pub kind: String,
+    pub message: String,
+    //Where it was raised, or None until an error() value is thrown
+    pub token: Option<Token>,
+}
+
+impl LoxError {
+    pub fn new(kind: &str, message: String, token: Option<Token>) -> Self {
+        LoxError {
+            kind: kind.to_string(),
+            message,
+            token,
+        }
+    }
+
+    pub fn line(&self) -> Option<usize> {
+        self.token.as_ref().map(|token| token.line)
+    }
+}
+
+impl fmt::Display for LoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.kind, self.message)
+    }
+}
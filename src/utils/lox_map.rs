@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+
+use super::literal_value::LiteralValue;
+
+/// The subset of values that can be map keys. Lists, maps and functions
+/// are mutable or have no meaningful equality, so they are rejected.
+/// Numbers are keyed by their bits, with -0 folded into 0 and NaN refused,
+/// which gives floats the total `Eq` that hashing needs.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MapKey {
+    Number(u64),
+    Boolean(bool),
+    String(String),
+    Nil,
+}
+
+impl MapKey {
+    pub fn from_value(value: &LiteralValue) -> Result<MapKey, String> {
+        match value {
+            LiteralValue::Float(number) if number.is_nan() => {
+                Err("NaN cannot be used as a map key".to_string())
+            }
+            LiteralValue::Float(number) => Ok(MapKey::Number((number + 0.0).to_bits())),
+            LiteralValue::Boolean(value) => Ok(MapKey::Boolean(*value)),
+            LiteralValue::String(value) => Ok(MapKey::String(value.clone())),
+            LiteralValue::Nil => Ok(MapKey::Nil),
+            other => Err(format!("{} cannot be used as a map key", other.type_name())),
+        }
+    }
+
+    pub fn to_value(&self) -> LiteralValue {
+        match self {
+            MapKey::Number(bits) => LiteralValue::Float(f64::from_bits(*bits)),
+            MapKey::Boolean(value) => LiteralValue::Boolean(*value),
+            MapKey::String(value) => LiteralValue::String(value.clone()),
+            MapKey::Nil => LiteralValue::Nil,
+        }
+    }
+}
+
+/// Hash map that remembers insertion order. Overwriting a key keeps its
+/// original position, removing it shifts the later entries down.
+#[derive(Debug, Clone, Default)]
+pub struct LoxMap {
+    entries: Vec<(MapKey, LiteralValue)>,
+    positions: HashMap<MapKey, usize>,
+}
+
+impl LoxMap {
+    pub fn new() -> Self {
+        LoxMap {
+            entries: Vec::new(),
+            positions: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, key: &MapKey) -> Option<&LiteralValue> {
+        self.positions
+            .get(key)
+            .map(|position| &self.entries[*position].1)
+    }
+
+    pub fn contains_key(&self, key: &MapKey) -> bool {
+        self.positions.contains_key(key)
+    }
+
+    pub fn insert(&mut self, key: MapKey, value: LiteralValue) {
+        match self.positions.get(&key) {
+            Some(position) => self.entries[*position].1 = value,
+            None => {
+                self.positions.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: &MapKey) -> Option<LiteralValue> {
+        let position = self.positions.remove(key)?;
+        let (_, value) = self.entries.remove(position);
+        for (key, _) in &self.entries[position..] {
+            if let Some(later) = self.positions.get_mut(key) {
+                *later -= 1;
+            }
+        }
+        Some(value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(MapKey, LiteralValue)> {
+        self.entries.iter()
+    }
+
+    /// Same keys, with values `equal` accepts. Lets a caller comparing
+    /// nested collections carry its own state through the values.
+    pub fn equal_by(
+        &self,
+        other: &Self,
+        mut equal: impl FnMut(&LiteralValue, &LiteralValue) -> bool,
+    ) -> bool {
+        self.len() == other.len()
+            && self.iter().all(|(key, value)| match other.get(key) {
+                Some(other) => equal(value, other),
+                None => false,
+            })
+    }
+}
+
+impl PartialEq for LoxMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.equal_by(other, |left, right| left == right)
+    }
+}
@@ -0,0 +1,39 @@
+/// Lazy arithmetic progression made by `range()`. Only the bounds are
+/// stored, elements are computed on demand.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoxRange {
+    pub start: f64,
+    pub end: f64,
+    pub step: f64,
+}
+
+impl LoxRange {
+    pub fn new(start: f64, end: f64, step: f64) -> Result<Self, String> {
+        if step == 0.0 || step.is_nan() {
+            return Err("range() step cannot be zero".to_string());
+        }
+        Ok(LoxRange { start, end, step })
+    }
+
+    pub fn len(&self) -> usize {
+        let count = ((self.end - self.start) / self.step).ceil();
+        match count > 0.0 {
+            true => count as usize,
+            false => 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, position: usize) -> f64 {
+        self.start + self.step * position as f64
+    }
+}
+
+impl std::fmt::Display for LoxRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "range({}, {}, {})", self.start, self.end, self.step)
+    }
+}
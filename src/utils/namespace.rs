@@ -0,0 +1,27 @@
+use std::collections::HashMap;
+
+use super::literal_value::LiteralValue;
+
+/// A named bag of values reached with `name.member`, such as `math.sqrt`.
+#[derive(Debug, Clone)]
+pub struct Namespace {
+    pub name: String,
+    members: HashMap<String, LiteralValue>,
+}
+
+impl Namespace {
+    pub fn new(name: &str) -> Self {
+        Namespace {
+            name: name.to_string(),
+            members: HashMap::new(),
+        }
+    }
+
+    pub fn define(&mut self, name: &str, value: LiteralValue) {
+        self.members.insert(name.to_string(), value);
+    }
+
+    pub fn get(&self, name: &str) -> Option<LiteralValue> {
+        self.members.get(name).cloned()
+    }
+}
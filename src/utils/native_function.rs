@@ -0,0 +1,67 @@
+use std::ops::RangeInclusive;
+
+use super::{literal_value::LiteralValue, random::Random};
+
+//Errors are plain messages, the interpreter reports them at the call site
+pub type NativeFn = fn(&mut NativeContext, Vec<LiteralValue>) -> Result<LiteralValue, String>;
+
+/// State owned by one interpreter that natives may read or change, so two
+/// interpreters in the same process never share it.
+#[derive(Debug, Clone)]
+pub struct NativeContext {
+    pub random: Random,
+    //Off unless the host opts in, see `Interpreter::set_filesystem`
+    pub filesystem: bool,
+}
+
+impl NativeContext {
+    pub fn new() -> Self {
+        NativeContext {
+            random: Random::from_time(),
+            filesystem: false,
+        }
+    }
+}
+
+impl Default for NativeContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A builtin implemented in Rust and exposed to Lox as a global.
+#[derive(Debug, Clone)]
+pub struct NativeFunction {
+    pub name: &'static str,
+    pub arity: RangeInclusive<usize>,
+    pub function: NativeFn,
+}
+
+impl NativeFunction {
+    pub fn new(name: &'static str, arity: RangeInclusive<usize>, function: NativeFn) -> Self {
+        NativeFunction {
+            name,
+            arity,
+            function,
+        }
+    }
+
+    //`receivers` leading arguments were supplied by a method's object, not the caller
+    pub fn check_arity(&self, given: usize, receivers: usize) -> Result<(), String> {
+        if self.arity.contains(&given) {
+            return Ok(());
+        }
+        let (min, max) = (self.arity.start() - receivers, self.arity.end() - receivers);
+        let expected = match (min == max, *self.arity.end() == usize::MAX) {
+            (true, _) => min.to_string(),
+            (false, true) => format!("at least {}", min),
+            (false, false) => format!("{} to {}", min, max),
+        };
+        Err(format!(
+            "{}() expects {} arguments but got {}",
+            self.name,
+            expected,
+            given - receivers
+        ))
+    }
+}
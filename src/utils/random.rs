@@ -0,0 +1,75 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// SplitMix64, small and fast with good enough statistics for scripts.
+/// The same seed always produces the same sequence on every platform.
+#[derive(Debug, Clone)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Random { state: seed }
+    }
+
+    //Seeded from the clock, for when reproducibility was not asked for
+    pub fn from_time() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as u64)
+            .unwrap_or(0);
+        Random::new(nanos)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    //Uniform in [0, 1), from the top 53 bits so every value is exact
+    pub fn next_float(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    //Uniform in [0, bound), bound must not be 0. Lemire's multiply-shift:
+    //the high half of a random number times bound, redrawing the few
+    //numbers whose low half would land in an over-represented slice
+    pub fn below(&mut self, bound: u64) -> u64 {
+        let mut product = self.next_u64() as u128 * bound as u128;
+        if (product as u64) < bound {
+            let threshold = bound.wrapping_neg() % bound;
+            while (product as u64) < threshold {
+                product = self.next_u64() as u128 * bound as u128;
+            }
+        }
+        (product >> 64) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn below_stays_under_the_bound() {
+        let mut random = Random::new(7);
+        for bound in [1, 2, 3, 7, 1 << 32, u64::MAX - 1, u64::MAX] {
+            for _ in 0..1000 {
+                assert!(random.below(bound) < bound);
+            }
+        }
+    }
+
+    #[test]
+    fn below_covers_every_value_evenly() {
+        let mut random = Random::new(42);
+        let mut counts = [0usize; 6];
+        for _ in 0..60_000 {
+            counts[random.below(6) as usize] += 1;
+        }
+        assert!(counts.iter().all(|&count| (9_000..11_000).contains(&count)));
+    }
+}
@@ -5,19 +5,27 @@ use std::collections::HashMap;
 pub static KEYWORDS: Lazy<HashMap<&'static str, TokenType>> = Lazy::new(|| {
     let mut m = HashMap::new();
     m.insert("and", TokenType::And);
+    m.insert("break", TokenType::Break);
+    m.insert("catch", TokenType::Catch);
     m.insert("class", TokenType::Class);
+    m.insert("continue", TokenType::Continue);
     m.insert("else", TokenType::Else);
     m.insert("false", TokenType::False);
+    m.insert("finally", TokenType::Finally);
     m.insert("for", TokenType::For);
     m.insert("fun", TokenType::Fun);
     m.insert("if", TokenType::If);
+    m.insert("import", TokenType::Import);
+    m.insert("in", TokenType::In);
     m.insert("nil", TokenType::Nil);
     m.insert("or", TokenType::Or);
     m.insert("print", TokenType::Print);
     m.insert("return", TokenType::Return);
     m.insert("super", TokenType::Super);
     m.insert("this", TokenType::This);
+    m.insert("throw", TokenType::Throw);
     m.insert("true", TokenType::True);
+    m.insert("try", TokenType::Try);
     m.insert("var", TokenType::Var);
     m.insert("while", TokenType::While);
     m
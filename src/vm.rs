@@ -0,0 +1,7 @@
+pub mod chunk;
+pub mod compiler;
+pub mod disassembler;
+pub mod function;
+pub mod loxc;
+pub mod opcode;
+pub mod vm;
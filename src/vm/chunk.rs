@@ -0,0 +1,91 @@
+use std::rc::Rc;
+
+use crate::{scanner::token::Token, utils::literal_value::LiteralValue};
+
+use super::{function::FunctionProto, opcode::OpCode};
+
+/// An entry of a chunk's constant pool.
+#[derive(Debug, Clone)]
+pub enum Constant {
+    //Numbers and strings, including the names of globals and members
+    Value(LiteralValue),
+    Function(Rc<FunctionProto>),
+    //Names of the named arguments of a call, in the order they were passed
+    Names(Rc<[String]>),
+}
+
+/// Compiled code of one function with what it refers to: the constants
+/// its instructions index, the source line of every byte, and the token
+/// of every instruction that can fail so errors point where the
+/// tree-walker would.
+#[derive(Debug, Clone, Default)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub constants: Vec<Constant>,
+    pub lines: Vec<usize>,
+    //By offset of the instruction, in increasing order
+    pub sites: Vec<(usize, Token)>,
+}
+
+impl Chunk {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write(&mut self, byte: u8, line: usize) {
+        self.code.push(byte);
+        self.lines.push(line);
+    }
+
+    pub fn write_op(&mut self, op: OpCode, line: usize) {
+        self.write(op as u8, line);
+    }
+
+    pub fn write_u16(&mut self, value: u16, line: usize) {
+        for byte in value.to_be_bytes() {
+            self.write(byte, line);
+        }
+    }
+
+    pub fn read_u16(&self, offset: usize) -> u16 {
+        u16::from_be_bytes([self.code[offset], self.code[offset + 1]])
+    }
+
+    //Index of the constant, reusing an equal number or string already there
+    pub fn add_constant(&mut self, constant: Constant) -> usize {
+        if let Constant::Value(value) = &constant {
+            let existing = self.constants.iter().position(|other| match other {
+                Constant::Value(other) => same_constant(value, other),
+                _ => false,
+            });
+            if let Some(index) = existing {
+                return index;
+            }
+        }
+        self.constants.push(constant);
+        self.constants.len() - 1
+    }
+
+    pub fn add_site(&mut self, offset: usize, token: Token) {
+        self.sites.push((offset, token));
+    }
+
+    //Token of the instruction at `offset`
+    pub fn site(&self, offset: usize) -> &Token {
+        match self.sites.binary_search_by_key(&offset, |(site, _)| *site) {
+            Ok(index) => &self.sites[index].1,
+            Err(_) => panic!("no source token recorded for the instruction at {}", offset),
+        }
+    }
+}
+
+//Equal and of the same kind, so 0 and -0 or 1 and "1" stay apart
+fn same_constant(left: &LiteralValue, right: &LiteralValue) -> bool {
+    match (left, right) {
+        (LiteralValue::Float(left), LiteralValue::Float(right)) => {
+            left.to_bits() == right.to_bits()
+        }
+        (LiteralValue::String(left), LiteralValue::String(right)) => left == right,
+        _ => false,
+    }
+}
@@ -0,0 +1,994 @@
+use std::rc::Rc;
+
+use crate::{
+    parser::{
+        expression::{Expression, Parameter},
+        statement::Statement,
+    },
+    scanner::{token::Token, token_type::TokenType},
+    utils::literal_value::LiteralValue,
+};
+
+use super::{
+    chunk::{Chunk, Constant},
+    function::{FunctionProto, Param},
+    opcode::OpCode,
+};
+
+/// A program the bytecode can't express, like a function with more than 256
+/// variables. Reported like a parse error.
+#[derive(Debug)]
+pub struct CompileError {
+    pub line: usize,
+    //None for limits of a whole function or statement, reported at the line
+    pub token: Option<Box<Token>>,
+    pub message: String,
+}
+
+/// Compiles a script or module body into the function the VM starts with.
+pub fn compile(statements: &[Statement]) -> Result<FunctionProto, CompileError> {
+    let mut compiler = Compiler {
+        functions: vec![FunctionState::new(None)],
+        line: 1,
+    };
+    for statement in statements {
+        compiler.statement(statement)?;
+    }
+    compiler.emit(OpCode::Nil);
+    compiler.emit(OpCode::Return);
+    let state = compiler
+        .functions
+        .pop()
+        .expect("the script is always compiling");
+    Ok(state.proto)
+}
+
+struct Local {
+    name: String,
+    depth: usize,
+    //Read by a closure, so leaving its scope has to close it
+    captured: bool,
+}
+
+struct UpvalueRef {
+    index: u8,
+    //Whether it is a local of the enclosing function or one of its upvalues
+    is_local: bool,
+}
+
+struct LoopContext {
+    //Locals where break and continue jump to, they drop the ones after
+    break_locals: usize,
+    continue_locals: usize,
+    //Try statements around the loop, break and continue leave the ones after
+    trys: usize,
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+//A try statement whose body or catch is being compiled
+struct TryContext<'a> {
+    locals: usize,
+    //Handlers of it on the VM at this point
+    handlers: usize,
+    finally: Option<&'a [Statement]>,
+}
+
+struct FunctionState<'a> {
+    proto: FunctionProto,
+    //Slot 0 holds the function being called
+    locals: Vec<Local>,
+    upvalues: Vec<UpvalueRef>,
+    scope_depth: usize,
+    loops: Vec<LoopContext>,
+    trys: Vec<TryContext<'a>>,
+}
+
+impl FunctionState<'_> {
+    fn new(name: Option<String>) -> Self {
+        FunctionState {
+            proto: FunctionProto {
+                name,
+                params: Vec::new(),
+                upvalues: 0,
+                chunk: Chunk::new(),
+            },
+            locals: vec![Local {
+                name: String::new(),
+                depth: 0,
+                captured: false,
+            }],
+            upvalues: Vec::new(),
+            scope_depth: 0,
+            loops: Vec::new(),
+            trys: Vec::new(),
+        }
+    }
+}
+
+//Where a variable lives
+enum Variable {
+    Local(u8),
+    Upvalue(u8),
+    Global(u16),
+}
+
+struct Compiler<'a> {
+    //Innermost last, the script first
+    functions: Vec<FunctionState<'a>>,
+    //Line of the last token seen, given to the bytes emitted
+    line: usize,
+}
+
+impl<'a> Compiler<'a> {
+    fn current(&mut self) -> &mut FunctionState<'a> {
+        self.functions
+            .last_mut()
+            .expect("a function is always compiling")
+    }
+
+    fn chunk(&mut self) -> &mut Chunk {
+        &mut self.current().proto.chunk
+    }
+
+    fn emit(&mut self, op: OpCode) {
+        let line = self.line;
+        self.chunk().write_op(op, line);
+    }
+
+    //An instruction that can fail, errors in it are reported at `token`
+    fn emit_at(&mut self, op: OpCode, token: &Token) {
+        self.line = token.line;
+        let offset = self.chunk().code.len();
+        self.chunk().add_site(offset, token.clone());
+        self.emit(op);
+    }
+
+    fn emit_byte(&mut self, byte: u8) {
+        let line = self.line;
+        self.chunk().write(byte, line);
+    }
+
+    fn emit_u16(&mut self, value: u16) {
+        let line = self.line;
+        self.chunk().write_u16(value, line);
+    }
+
+    //Emits a jump to patch later, returning where its offset goes
+    fn emit_jump(&mut self, op: OpCode) -> usize {
+        self.emit(op);
+        self.emit_u16(u16::MAX);
+        self.chunk().code.len() - 2
+    }
+
+    fn patch_jump(&mut self, at: usize) -> Result<(), CompileError> {
+        let distance = self.chunk().code.len() - at - 2;
+        let distance = self.check_u16(distance, "Too much code to jump over")?;
+        let [high, low] = distance.to_be_bytes();
+        let code = &mut self.chunk().code;
+        code[at] = high;
+        code[at + 1] = low;
+        Ok(())
+    }
+
+    fn emit_loop(&mut self, start: usize) -> Result<(), CompileError> {
+        self.emit(OpCode::Loop);
+        let distance = self.chunk().code.len() + 2 - start;
+        let distance = self.check_u16(distance, "Loop body too large")?;
+        self.emit_u16(distance);
+        Ok(())
+    }
+
+    fn constant(&mut self, constant: Constant) -> Result<u16, CompileError> {
+        let index = self.chunk().add_constant(constant);
+        self.check_u16(index, "Too many constants in one function")
+    }
+
+    fn name_constant(&mut self, name: &str) -> Result<u16, CompileError> {
+        self.constant(Constant::Value(LiteralValue::String(name.to_string())))
+    }
+
+    //Errors without a token of their own are reported at the current line
+    fn error(&self, message: &str) -> CompileError {
+        CompileError {
+            line: self.line,
+            token: None,
+            message: message.to_string(),
+        }
+    }
+
+    fn check_u16(&self, value: usize, message: &str) -> Result<u16, CompileError> {
+        u16::try_from(value).map_err(|_| self.error(message))
+    }
+
+    fn check_u8(&self, value: usize, message: &str) -> Result<u8, CompileError> {
+        u8::try_from(value).map_err(|_| self.error(message))
+    }
+
+    fn statement(&mut self, statement: &'a Statement) -> Result<(), CompileError> {
+        match statement {
+            Statement::Expr { expression } => {
+                self.expression(expression)?;
+                self.emit(OpCode::Pop);
+            }
+            Statement::Print { expression } => {
+                self.expression(expression)?;
+                self.emit(OpCode::Print);
+            }
+            Statement::Var { name, initializer } => {
+                self.line = name.line;
+                //A function declared in a block sees itself, other initializers
+                //see what the name meant before
+                let recursive = matches!(initializer, Expression::Lambda { name: Some(own), .. } if own.lexame == name.lexame);
+                if self.current().scope_depth > 0 && recursive {
+                    self.add_local(&name.lexame)?;
+                    self.expression(initializer)?;
+                } else {
+                    self.expression(initializer)?;
+                    self.define_variable(name)?;
+                }
+            }
+            Statement::Block { statements } => self.block(statements)?,
+            Statement::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(condition)?;
+                let to_else = self.emit_jump(OpCode::JumpIfFalse);
+                self.emit(OpCode::Pop);
+                self.statement(then_branch)?;
+                let to_end = self.emit_jump(OpCode::Jump);
+                self.patch_jump(to_else)?;
+                self.emit(OpCode::Pop);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch)?;
+                }
+                self.patch_jump(to_end)?;
+            }
+            Statement::While {
+                condition,
+                body,
+                increment,
+            } => self.while_statement(condition, body, increment.as_ref())?,
+            Statement::ForIn {
+                name,
+                iterable,
+                body,
+            } => self.for_in_statement(name, iterable, body)?,
+            Statement::Break { keyword } | Statement::Continue { keyword } => {
+                self.line = keyword.line;
+                self.loop_jump(keyword.token_type == TokenType::Break)?;
+            }
+            Statement::Return {
+                keyword,
+                value,
+                tail,
+            } => {
+                self.line = keyword.line;
+                self.return_statement(value.as_ref(), *tail)?;
+            }
+            Statement::Throw { keyword, value } => {
+                self.expression(value)?;
+                self.emit_at(OpCode::Throw, keyword);
+            }
+            Statement::Try {
+                body,
+                catch,
+                finally,
+            } => self.try_statement(body, catch.as_ref(), finally.as_deref())?,
+            Statement::Import { path, alias, .. } => {
+                let constant = self.constant(Constant::Value(path.literal.clone()))?;
+                self.emit_at(OpCode::Import, path);
+                self.emit_u16(constant);
+                self.define_variable(alias)?;
+            }
+            Statement::FromImport { path, names, .. } => {
+                let constant = self.constant(Constant::Value(path.literal.clone()))?;
+                self.emit_at(OpCode::Import, path);
+                self.emit_u16(constant);
+                //In a block the module stays in a slot of its own until the block
+                //ends, and the names after the first read a copy of it, since
+                //ImportName looks at the top of the stack
+                let global = self.current().scope_depth == 0;
+                let module = match global {
+                    true => None,
+                    false => Some(self.add_local("(module)")?),
+                };
+                for (index, name) in names.iter().enumerate() {
+                    if let (Some(slot), true) = (module, index > 0) {
+                        self.emit(OpCode::GetLocal);
+                        self.emit_byte(slot);
+                        self.add_local("(module)")?;
+                    }
+                    let constant = self.name_constant(&name.lexame)?;
+                    self.emit_at(OpCode::ImportName, name);
+                    self.emit_u16(constant);
+                    self.define_variable(name)?;
+                }
+                if global {
+                    self.emit(OpCode::Pop);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn block(&mut self, statements: &'a [Statement]) -> Result<(), CompileError> {
+        self.begin_scope();
+        for statement in statements {
+            self.statement(statement)?;
+        }
+        self.end_scope();
+        Ok(())
+    }
+
+    fn begin_scope(&mut self) {
+        self.current().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self) {
+        let state = self.current();
+        state.scope_depth -= 1;
+        let depth = state.scope_depth;
+        let keep = state
+            .locals
+            .iter()
+            .rposition(|local| local.depth <= depth)
+            .map_or(0, |index| index + 1);
+        self.discard_locals(keep);
+        self.current().locals.truncate(keep);
+    }
+
+    //Emits what drops the locals from `keep` on, without forgetting them
+    fn discard_locals(&mut self, keep: usize) {
+        let captured: Vec<bool> = self.current().locals[keep..]
+            .iter()
+            .rev()
+            .map(|local| local.captured)
+            .collect();
+        for captured in captured {
+            match captured {
+                true => self.emit(OpCode::CloseUpvalue),
+                false => self.emit(OpCode::Pop),
+            }
+        }
+    }
+
+    //The value on top of the stack becomes the local's slot
+    fn add_local(&mut self, name: &str) -> Result<u8, CompileError> {
+        let count = self.current().locals.len();
+        let slot = self.check_u8(count, "Too many local variables in function")?;
+        let depth = self.current().scope_depth;
+        self.current().locals.push(Local {
+            name: name.to_string(),
+            depth,
+            captured: false,
+        });
+        Ok(slot)
+    }
+
+    //Declares `name` holding the value on top of the stack
+    fn define_variable(&mut self, name: &Token) -> Result<(), CompileError> {
+        self.line = name.line;
+        if self.current().scope_depth > 0 {
+            self.add_local(&name.lexame)?;
+            return Ok(());
+        }
+        let constant = self.name_constant(&name.lexame)?;
+        self.emit(OpCode::DefineGlobal);
+        self.emit_u16(constant);
+        Ok(())
+    }
+
+    fn resolve(&mut self, name: &str) -> Result<Variable, CompileError> {
+        let innermost = self.functions.len() - 1;
+        if let Some(slot) = self.resolve_local(innermost, name) {
+            return Ok(Variable::Local(slot));
+        }
+        if let Some(index) = self.resolve_upvalue(innermost, name)? {
+            return Ok(Variable::Upvalue(index));
+        }
+        Ok(Variable::Global(self.name_constant(name)?))
+    }
+
+    fn resolve_local(&self, function: usize, name: &str) -> Option<u8> {
+        self.functions[function]
+            .locals
+            .iter()
+            .rposition(|local| local.name == name)
+            .map(|slot| slot as u8)
+    }
+
+    fn resolve_upvalue(&mut self, function: usize, name: &str) -> Result<Option<u8>, CompileError> {
+        if function == 0 {
+            return Ok(None);
+        }
+        if let Some(slot) = self.resolve_local(function - 1, name) {
+            self.functions[function - 1].locals[slot as usize].captured = true;
+            return self.add_upvalue(function, slot, true).map(Some);
+        }
+        match self.resolve_upvalue(function - 1, name)? {
+            Some(index) => self.add_upvalue(function, index, false).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn add_upvalue(
+        &mut self,
+        function: usize,
+        index: u8,
+        is_local: bool,
+    ) -> Result<u8, CompileError> {
+        let upvalues = &self.functions[function].upvalues;
+        if let Some(existing) = upvalues
+            .iter()
+            .position(|upvalue| upvalue.index == index && upvalue.is_local == is_local)
+        {
+            return Ok(existing as u8);
+        }
+        let position = self.check_u8(upvalues.len(), "Too many captured variables in function")?;
+        self.functions[function]
+            .upvalues
+            .push(UpvalueRef { index, is_local });
+        Ok(position)
+    }
+
+    fn while_statement(
+        &mut self,
+        condition: &'a Expression,
+        body: &'a Statement,
+        increment: Option<&'a Expression>,
+    ) -> Result<(), CompileError> {
+        let start = self.chunk().code.len();
+        self.expression(condition)?;
+        let to_exit = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit(OpCode::Pop);
+        let locals = self.current().locals.len();
+        self.loop_body(body, locals)?;
+        if let Some(increment) = increment {
+            self.expression(increment)?;
+            self.emit(OpCode::Pop);
+        }
+        self.emit_loop(start)?;
+        self.patch_jump(to_exit)?;
+        self.emit(OpCode::Pop);
+        self.end_loop()
+    }
+
+    //The iterator sits in a slot of its own below the loop variable
+    fn for_in_statement(
+        &mut self,
+        name: &'a Token,
+        iterable: &'a Expression,
+        body: &'a Statement,
+    ) -> Result<(), CompileError> {
+        self.begin_scope();
+        self.expression(iterable)?;
+        self.emit_at(OpCode::IterStart, name);
+        self.add_local("(iterator)")?;
+        let locals = self.current().locals.len();
+        let start = self.chunk().code.len();
+        let to_exit = self.emit_jump(OpCode::IterNext);
+        //A scope per iteration, so closures capture a fresh variable each time
+        self.begin_scope();
+        self.add_local(&name.lexame)?;
+        self.loop_body(body, locals)?;
+        self.end_scope();
+        self.emit_loop(start)?;
+        self.patch_jump(to_exit)?;
+        self.end_loop()?;
+        self.end_scope();
+        Ok(())
+    }
+
+    //Compiles a loop body, leaving its continues pointing right after it.
+    //Its breaks leave with `break_locals` locals
+    fn loop_body(&mut self, body: &'a Statement, break_locals: usize) -> Result<(), CompileError> {
+        let state = self.current();
+        let context = LoopContext {
+            break_locals,
+            continue_locals: state.locals.len(),
+            trys: state.trys.len(),
+            breaks: Vec::new(),
+            continues: Vec::new(),
+        };
+        state.loops.push(context);
+        self.statement(body)?;
+        let continues = std::mem::take(
+            &mut self
+                .current()
+                .loops
+                .last_mut()
+                .expect("in a loop")
+                .continues,
+        );
+        for jump in continues {
+            self.patch_jump(jump)?;
+        }
+        Ok(())
+    }
+
+    //Points the breaks of the innermost loop here
+    fn end_loop(&mut self) -> Result<(), CompileError> {
+        let context = self.current().loops.pop().expect("in a loop");
+        for jump in context.breaks {
+            self.patch_jump(jump)?;
+        }
+        Ok(())
+    }
+
+    fn loop_jump(&mut self, is_break: bool) -> Result<(), CompileError> {
+        let state = self.current();
+        let context = state
+            .loops
+            .last()
+            .expect("the parser keeps break and continue in loops");
+        let locals = match is_break {
+            true => context.break_locals,
+            false => context.continue_locals,
+        };
+        let trys = context.trys;
+        let top = state.locals.len();
+        let top = self.leave_trys(trys, Some(top))?;
+        self.discard_locals_between(locals, top);
+        let jump = self.emit_jump(OpCode::Jump);
+        let context = self.current().loops.last_mut().expect("in a loop");
+        match is_break {
+            true => context.breaks.push(jump),
+            false => context.continues.push(jump),
+        }
+        Ok(())
+    }
+
+    //Drops the locals in keep..top, the ones above already being gone
+    fn discard_locals_between(&mut self, keep: usize, top: usize) {
+        let hidden = self.current().locals.split_off(top);
+        self.discard_locals(keep);
+        self.current().locals.extend(hidden);
+    }
+
+    /// Leaves the try statements from `down_to` on, innermost first, the way
+    /// a jump out of them has to: their handlers are dropped and their
+    /// finally blocks run. With `top`, the locals inside each one are
+    /// dropped first and the count of locals left is returned.
+    fn leave_trys(
+        &mut self,
+        down_to: usize,
+        mut top: Option<usize>,
+    ) -> Result<usize, CompileError> {
+        let count = self.current().trys.len();
+        for index in (down_to..count).rev() {
+            let context = &self.current().trys[index];
+            let (locals, handlers, finally) = (context.locals, context.handlers, context.finally);
+            if let Some(current) = top {
+                self.discard_locals_between(locals, current);
+                top = Some(locals);
+            }
+            for _ in 0..handlers {
+                self.emit(OpCode::PopHandler);
+            }
+            if let Some(finally) = finally {
+                //Compiled as if in the statement around the try, which is
+                //where it runs from
+                let trys = self.current().trys.split_off(index);
+                let locals = match top {
+                    Some(top) => self.current().locals.split_off(top),
+                    None => Vec::new(),
+                };
+                let result = self.block(finally);
+                self.current().locals.extend(locals);
+                self.current().trys.extend(trys);
+                result?;
+            }
+        }
+        Ok(top.unwrap_or_else(|| self.current().locals.len()))
+    }
+
+    fn return_statement(
+        &mut self,
+        value: Option<&'a Expression>,
+        tail: bool,
+    ) -> Result<(), CompileError> {
+        if self.current().trys.is_empty() {
+            match value {
+                Some(value) if tail => self.tail_return(value)?,
+                Some(value) => {
+                    self.expression(value)?;
+                    self.emit(OpCode::Return);
+                }
+                None => {
+                    self.emit(OpCode::Nil);
+                    self.emit(OpCode::Return);
+                }
+            }
+            return Ok(());
+        }
+        //Kept in a slot while the finally blocks around it run
+        match value {
+            Some(value) => self.expression(value)?,
+            None => self.emit(OpCode::Nil),
+        }
+        let slot = self.add_local("(return)")?;
+        self.leave_trys(0, None)?;
+        self.emit(OpCode::GetLocal);
+        self.emit_byte(slot);
+        self.emit(OpCode::Return);
+        self.current().locals.pop();
+        Ok(())
+    }
+
+    //Every way through a returned expression ends in a return, through a
+    //tail call where it ends in a call
+    fn tail_return(&mut self, value: &'a Expression) -> Result<(), CompileError> {
+        match value {
+            Expression::Call {
+                callee,
+                paren,
+                arguments,
+                named,
+            } => self.call(callee, paren, arguments, named, true),
+            Expression::Grouping { expression } => self.tail_return(expression),
+            Expression::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(condition)?;
+                let to_else = self.emit_jump(OpCode::JumpIfFalse);
+                self.emit(OpCode::Pop);
+                self.tail_return(then_branch)?;
+                self.patch_jump(to_else)?;
+                self.emit(OpCode::Pop);
+                self.tail_return(else_branch)
+            }
+            other => {
+                self.expression(other)?;
+                self.emit(OpCode::Return);
+                Ok(())
+            }
+        }
+    }
+
+    /// try/catch/finally. The body runs under a handler for the catch and
+    /// one for the finally, the catch under the finally's. A throw the
+    /// catch doesn't handle runs the finally and is thrown again.
+    fn try_statement(
+        &mut self,
+        body: &'a [Statement],
+        catch: Option<&'a (Token, Vec<Statement>)>,
+        finally: Option<&'a [Statement]>,
+    ) -> Result<(), CompileError> {
+        let locals = self.current().locals.len();
+        let to_finally = finally.map(|_| self.emit_jump(OpCode::PushHandler));
+        let to_catch = catch.map(|_| self.emit_jump(OpCode::PushHandler));
+        let handlers = to_finally.iter().count() + to_catch.iter().count();
+        self.current().trys.push(TryContext {
+            locals,
+            handlers,
+            finally,
+        });
+        self.block(body)?;
+        if let (Some(to_catch), Some((name, handler))) = (to_catch, catch) {
+            self.emit(OpCode::PopHandler);
+            let over_catch = self.emit_jump(OpCode::Jump);
+            self.patch_jump(to_catch)?;
+            self.current().trys.last_mut().expect("in a try").handlers -= 1;
+            //The VM leaves the thrown value where the catch variable goes
+            self.begin_scope();
+            self.line = name.line;
+            self.add_local(&name.lexame)?;
+            for statement in handler {
+                self.statement(statement)?;
+            }
+            self.end_scope();
+            self.patch_jump(over_catch)?;
+        }
+        self.current().trys.pop();
+
+        if let (Some(to_finally), Some(finally)) = (to_finally, finally) {
+            self.emit(OpCode::PopHandler);
+            self.block(finally)?;
+            let over_rethrow = self.emit_jump(OpCode::Jump);
+            self.patch_jump(to_finally)?;
+            self.begin_scope();
+            let slot = self.add_local("(thrown)")?;
+            for statement in finally {
+                self.statement(statement)?;
+            }
+            self.emit(OpCode::Rethrow);
+            self.emit_byte(slot);
+            self.end_scope();
+            self.patch_jump(over_rethrow)?;
+        }
+        Ok(())
+    }
+
+    fn expression(&mut self, expression: &'a Expression) -> Result<(), CompileError> {
+        match expression {
+            Expression::Literal { value } => match value {
+                LiteralValue::Nil => self.emit(OpCode::Nil),
+                LiteralValue::Boolean(true) => self.emit(OpCode::True),
+                LiteralValue::Boolean(false) => self.emit(OpCode::False),
+                value => {
+                    let constant = self.constant(Constant::Value(value.clone()))?;
+                    self.emit(OpCode::Constant);
+                    self.emit_u16(constant);
+                }
+            },
+            Expression::Unary { operator, right } => {
+                self.expression(right)?;
+                match operator.token_type {
+                    TokenType::Bang => self.emit_at(OpCode::Not, operator),
+                    _ => self.emit_at(OpCode::Negate, operator),
+                }
+            }
+            Expression::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                self.expression(left)?;
+                self.expression(right)?;
+                let op = match operator.token_type {
+                    TokenType::Plus => OpCode::Add,
+                    TokenType::Minus => OpCode::Subtract,
+                    TokenType::Star => OpCode::Multiply,
+                    TokenType::Slash => OpCode::Divide,
+                    TokenType::Percent => OpCode::Modulo,
+                    TokenType::TildeSlash => OpCode::FloorDivide,
+                    TokenType::StarStar => OpCode::Power,
+                    TokenType::EqualEqual => OpCode::Equal,
+                    TokenType::BangEqual => OpCode::NotEqual,
+                    TokenType::Greater => OpCode::Greater,
+                    TokenType::GreaterEqual => OpCode::GreaterEqual,
+                    TokenType::Less => OpCode::Less,
+                    TokenType::LessEqual => OpCode::LessEqual,
+                    _ => {
+                        return Err(CompileError {
+                            line: operator.line,
+                            token: Some(Box::new(operator.clone())),
+                            message: format!("Unknown operator '{}'", operator.lexame),
+                        })
+                    }
+                };
+                self.emit_at(op, operator);
+            }
+            Expression::Grouping { expression } => self.expression(expression)?,
+            Expression::Variable { name } => {
+                self.line = name.line;
+                match self.resolve(&name.lexame)? {
+                    Variable::Local(slot) => {
+                        self.emit(OpCode::GetLocal);
+                        self.emit_byte(slot);
+                    }
+                    Variable::Upvalue(index) => {
+                        self.emit(OpCode::GetUpvalue);
+                        self.emit_byte(index);
+                    }
+                    Variable::Global(constant) => {
+                        self.emit_at(OpCode::GetGlobal, name);
+                        self.emit_u16(constant);
+                    }
+                }
+            }
+            Expression::Assign { name, value } => {
+                self.expression(value)?;
+                self.line = name.line;
+                match self.resolve(&name.lexame)? {
+                    Variable::Local(slot) => {
+                        self.emit(OpCode::SetLocal);
+                        self.emit_byte(slot);
+                    }
+                    Variable::Upvalue(index) => {
+                        self.emit(OpCode::SetUpvalue);
+                        self.emit_byte(index);
+                    }
+                    Variable::Global(constant) => {
+                        self.emit_at(OpCode::SetGlobal, name);
+                        self.emit_u16(constant);
+                    }
+                }
+            }
+            Expression::Logical {
+                left,
+                operator,
+                right,
+            } => {
+                self.expression(left)?;
+                self.line = operator.line;
+                let op = match operator.token_type {
+                    TokenType::Or => OpCode::JumpIfTrue,
+                    _ => OpCode::JumpIfFalse,
+                };
+                let to_end = self.emit_jump(op);
+                self.emit(OpCode::Pop);
+                self.expression(right)?;
+                self.patch_jump(to_end)?;
+            }
+            Expression::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(condition)?;
+                let to_else = self.emit_jump(OpCode::JumpIfFalse);
+                self.emit(OpCode::Pop);
+                self.expression(then_branch)?;
+                let to_end = self.emit_jump(OpCode::Jump);
+                self.patch_jump(to_else)?;
+                self.emit(OpCode::Pop);
+                self.expression(else_branch)?;
+                self.patch_jump(to_end)?;
+            }
+            Expression::Sequence { expressions } => {
+                for (index, expression) in expressions.iter().enumerate() {
+                    if index > 0 {
+                        self.emit(OpCode::Pop);
+                    }
+                    self.expression(expression)?;
+                }
+                if expressions.is_empty() {
+                    self.emit(OpCode::Nil);
+                }
+            }
+            Expression::List { elements } => {
+                for element in elements {
+                    self.expression(element)?;
+                }
+                let count = self.check_u16(elements.len(), "Too many elements in list literal")?;
+                self.emit(OpCode::List);
+                self.emit_u16(count);
+            }
+            Expression::Map { brace, entries } => {
+                for (key, value) in entries {
+                    self.expression(key)?;
+                    self.expression(value)?;
+                }
+                let count = self.check_u16(entries.len(), "Too many entries in map literal")?;
+                self.emit_at(OpCode::Map, brace);
+                self.emit_u16(count);
+            }
+            Expression::Index {
+                object,
+                bracket,
+                index,
+            } => {
+                self.expression(object)?;
+                self.expression(index)?;
+                self.emit_at(OpCode::Index, bracket);
+            }
+            Expression::SetIndex {
+                object,
+                bracket,
+                index,
+                value,
+            } => {
+                self.expression(object)?;
+                self.expression(index)?;
+                self.expression(value)?;
+                self.emit_at(OpCode::SetIndex, bracket);
+            }
+            Expression::Get { object, name } => {
+                self.expression(object)?;
+                let constant = self.name_constant(&name.lexame)?;
+                self.emit_at(OpCode::GetMember, name);
+                self.emit_u16(constant);
+            }
+            Expression::Interpolation { parts } => {
+                for part in parts {
+                    self.expression(part)?;
+                }
+                let count = self.check_u16(parts.len(), "Too many parts in string")?;
+                self.emit(OpCode::Interpolate);
+                self.emit_u16(count);
+            }
+            Expression::Lambda {
+                keyword,
+                name,
+                params,
+                body,
+            } => self.function(keyword, name.as_ref(), params, body)?,
+            Expression::Call {
+                callee,
+                paren,
+                arguments,
+                named,
+            } => self.call(callee, paren, arguments, named, false)?,
+        }
+        Ok(())
+    }
+
+    fn call(
+        &mut self,
+        callee: &'a Expression,
+        paren: &'a Token,
+        arguments: &'a [Expression],
+        named: &'a [(Token, Expression)],
+        tail: bool,
+    ) -> Result<(), CompileError> {
+        self.expression(callee)?;
+        for argument in arguments {
+            self.expression(argument)?;
+        }
+        for (_, argument) in named {
+            self.expression(argument)?;
+        }
+        self.line = paren.line;
+        let count = self.check_u8(arguments.len(), "Can't have more than 255 arguments")?;
+        if named.is_empty() {
+            let op = if tail { OpCode::TailCall } else { OpCode::Call };
+            self.emit_at(op, paren);
+            self.emit_byte(count);
+            return Ok(());
+        }
+        self.check_u8(named.len(), "Can't have more than 255 named arguments")?;
+        let names: Rc<[String]> = named.iter().map(|(name, _)| name.lexame.clone()).collect();
+        let constant = self.constant(Constant::Names(names))?;
+        let op = if tail {
+            OpCode::TailCallNamed
+        } else {
+            OpCode::CallNamed
+        };
+        self.emit_at(op, paren);
+        self.emit_byte(count);
+        self.emit_u16(constant);
+        Ok(())
+    }
+
+    /// Compiles a function literal into a constant of its own and the
+    /// instruction making a closure of it. Parameters are its first locals,
+    /// the code filling in defaults the caller left out comes first.
+    fn function(
+        &mut self,
+        keyword: &'a Token,
+        name: Option<&'a Token>,
+        params: &'a [Parameter],
+        body: &'a [Statement],
+    ) -> Result<(), CompileError> {
+        self.line = keyword.line;
+        self.functions
+            .push(FunctionState::new(name.map(|name| name.lexame.clone())));
+        self.begin_scope();
+        for param in params {
+            self.line = param.name.line;
+            self.add_local(&param.name.lexame)?;
+            self.current().proto.params.push(Param {
+                name: param.name.lexame.clone(),
+                default: param.default.is_some(),
+                rest: param.rest,
+            });
+        }
+        for (index, param) in params.iter().enumerate() {
+            if let Some(default) = &param.default {
+                let slot = (index + 1) as u8;
+                self.emit(OpCode::JumpIfBound);
+                self.emit_byte(slot);
+                self.emit_u16(u16::MAX);
+                let to_skip = self.chunk().code.len() - 2;
+                self.expression(default)?;
+                self.emit(OpCode::SetLocal);
+                self.emit_byte(slot);
+                self.emit(OpCode::Pop);
+                self.patch_jump(to_skip)?;
+            }
+        }
+        for statement in body {
+            self.statement(statement)?;
+        }
+        self.emit(OpCode::Nil);
+        self.emit(OpCode::Return);
+
+        let mut state = self.functions.pop().expect("compiling the function");
+        state.proto.upvalues = state.upvalues.len();
+        let constant = self.constant(Constant::Function(Rc::new(state.proto)))?;
+        self.line = keyword.line;
+        self.emit(OpCode::Closure);
+        self.emit_u16(constant);
+        for upvalue in state.upvalues {
+            self.emit_byte(upvalue.is_local as u8);
+            self.emit_byte(upvalue.index);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,155 @@
+use std::fmt::Write;
+
+use crate::utils::literal_value::LiteralValue;
+
+use super::{
+    chunk::{Chunk, Constant},
+    function::FunctionProto,
+    opcode::OpCode,
+};
+
+/// Lists the instructions of `function` under `name`, then those of every
+/// function it contains. Each line has the offset, the source line or `|`
+/// when it is the one of the instruction before, the opcode and its operands.
+pub fn disassemble(function: &FunctionProto, name: &str) -> String {
+    let chunk = &function.chunk;
+    let mut out = format!("== {} ==\n", name);
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        let same_line = offset > 0 && chunk.lines[offset] == chunk.lines[offset - 1];
+        let line = match same_line {
+            true => "|".to_string(),
+            false => chunk.lines[offset].to_string(),
+        };
+        let _ = writeln!(
+            out,
+            "{:04} {:>4} {}",
+            offset,
+            line,
+            instruction(chunk, offset)
+        );
+        offset += length(chunk, offset);
+    }
+    for constant in &chunk.constants {
+        if let Constant::Function(inner) = constant {
+            out.push('\n');
+            out.push_str(&disassemble(inner, &inner.display_name()));
+        }
+    }
+    out
+}
+
+/// The opcode at `offset` and its operands, with jumps resolved to the
+/// offset they land on. A Closure lists its captures on lines of their own.
+pub fn instruction(chunk: &Chunk, offset: usize) -> String {
+    let op = match OpCode::from_byte(chunk.code[offset]) {
+        Some(op) => op,
+        None => return format!("Unknown opcode {}", chunk.code[offset]),
+    };
+    let byte = |index: usize| chunk.code[offset + 1 + index] as usize;
+    let short = |index: usize| chunk.read_u16(offset + 1 + index) as usize;
+    let next = offset + 1 + op.operand_bytes();
+    let operands = match op {
+        OpCode::Constant
+        | OpCode::GetGlobal
+        | OpCode::SetGlobal
+        | OpCode::DefineGlobal
+        | OpCode::GetMember
+        | OpCode::Import
+        | OpCode::ImportName => {
+            format!("{:4} {}", short(0), constant(&chunk.constants[short(0)]))
+        }
+        OpCode::GetLocal
+        | OpCode::SetLocal
+        | OpCode::GetUpvalue
+        | OpCode::SetUpvalue
+        | OpCode::Call
+        | OpCode::TailCall
+        | OpCode::Rethrow => format!("{:4}", byte(0)),
+        OpCode::List | OpCode::Map | OpCode::Interpolate => format!("{:4}", short(0)),
+        OpCode::Jump
+        | OpCode::JumpIfFalse
+        | OpCode::JumpIfTrue
+        | OpCode::IterNext
+        | OpCode::PushHandler => format!("{:4} -> {:04}", offset, next + short(0)),
+        OpCode::Loop => format!("{:4} -> {:04}", offset, next - short(0)),
+        OpCode::JumpIfBound => format!("{:4} -> {:04}", byte(0), next + short(1)),
+        OpCode::CallNamed | OpCode::TailCallNamed => {
+            format!("{:4} {}", byte(0), constant(&chunk.constants[short(1)]))
+        }
+        OpCode::Closure => {
+            let mut operands = format!("{:4} {}", short(0), constant(&chunk.constants[short(0)]));
+            for capture in 0..upvalues(chunk, offset) {
+                let at = next + capture * 2;
+                let kind = match chunk.code[at] {
+                    1 => "local",
+                    _ => "upvalue",
+                };
+                let _ = write!(
+                    operands,
+                    "\n{:04}    | {:<16} {:4}",
+                    at,
+                    kind,
+                    chunk.code[at + 1]
+                );
+            }
+            operands
+        }
+        _ => String::new(),
+    };
+    format!("{:<16} {}", format!("{:?}", op), operands)
+        .trim_end()
+        .to_string()
+}
+
+/// The value stack as the trace shows it, bottom first. `labels` names the
+/// slots holding the script or a module being run, which would otherwise
+/// show as lambdas.
+pub fn stack(values: &[LiteralValue], labels: &[(usize, String)]) -> String {
+    let mut out = String::new();
+    for (slot, value) in values.iter().enumerate() {
+        let _ = match labels.iter().find(|(labelled, _)| *labelled == slot) {
+            Some((_, label)) => write!(out, "[ {} ]", label),
+            None => write!(out, "[ {} ]", shown(value)),
+        };
+    }
+    out
+}
+
+//Bytes taken by the instruction at `offset`, operands included
+fn length(chunk: &Chunk, offset: usize) -> usize {
+    match OpCode::from_byte(chunk.code[offset]) {
+        Some(op) => 1 + op.operand_bytes() + upvalues(chunk, offset) * 2,
+        None => 1,
+    }
+}
+
+//Captures following a Closure at `offset`, none for other instructions
+fn upvalues(chunk: &Chunk, offset: usize) -> usize {
+    if chunk.code[offset] != OpCode::Closure as u8 {
+        return 0;
+    }
+    match &chunk.constants[chunk.read_u16(offset + 1) as usize] {
+        Constant::Function(function) => function.upvalues,
+        _ => 0,
+    }
+}
+
+fn constant(constant: &Constant) -> String {
+    match constant {
+        Constant::Value(value) => shown(value),
+        Constant::Function(function) => match &function.name {
+            Some(name) => format!("<fn {}>", name),
+            None => "<lambda>".to_string(),
+        },
+        Constant::Names(names) => format!("({})", names.join(", ")),
+    }
+}
+
+//Strings quoted so they stand apart from names and numbers
+fn shown(value: &LiteralValue) -> String {
+    match value {
+        LiteralValue::String(text) => format!("{:?}", text),
+        other => other.to_string(),
+    }
+}
@@ -0,0 +1,163 @@
+use std::{cell::RefCell, collections::HashMap, fmt, path::PathBuf, rc::Rc};
+
+use crate::{
+    interpreter::lox_function::ParameterSpec,
+    natives,
+    utils::{literal_value::LiteralValue, namespace::Namespace},
+};
+
+use super::chunk::Chunk;
+
+/// A function as the compiler leaves it: its code and what calling it
+/// needs to know. The script and every module body are one too.
+#[derive(Debug, Clone)]
+pub struct FunctionProto {
+    //None for lambdas and top-level code
+    pub name: Option<String>,
+    pub params: Vec<Param>,
+    //How many variables of enclosing functions it captures
+    pub upvalues: usize,
+    pub chunk: Chunk,
+}
+
+impl FunctionProto {
+    //How it shows in values and tracebacks, like LoxFunction
+    pub fn display_name(&self) -> String {
+        match &self.name {
+            Some(name) => format!("{}()", name),
+            None => "<lambda>".to_string(),
+        }
+    }
+
+    //Whether every call can pass its arguments straight to the parameters
+    pub fn takes_plain_arguments(&self) -> bool {
+        self.params
+            .iter()
+            .all(|param| !param.default && !param.rest)
+    }
+}
+
+/// A parameter, its default is compiled into the start of the function.
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub name: String,
+    pub default: bool,
+    pub rest: bool,
+}
+
+impl ParameterSpec for Param {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn has_default(&self) -> bool {
+        self.default
+    }
+
+    fn is_rest(&self) -> bool {
+        self.rest
+    }
+}
+
+/// A variable a closure captured. It lives on the VM stack until the scope
+/// declaring it ends, then moves into the upvalue.
+#[derive(Debug)]
+pub enum Upvalue {
+    Open(usize),
+    Closed(LiteralValue),
+}
+
+/// A function value of the VM: the compiled function with the variables
+/// it captured and the module whose globals it sees.
+pub struct Closure {
+    pub function: Rc<FunctionProto>,
+    pub upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    pub module: Rc<Module>,
+}
+
+impl fmt::Debug for Closure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Closure({})", self.function.display_name())
+    }
+}
+
+/// The script or an imported file, with its own globals.
+pub struct Module {
+    //None outside of any file
+    pub path: Option<PathBuf>,
+    pub globals: RefCell<Globals>,
+}
+
+impl Module {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Module {
+            path,
+            globals: RefCell::new(Globals::new()),
+        }
+    }
+}
+
+/// A module's global variables over its own copy of the natives, the same
+/// two scopes the tree-walker gives it.
+pub struct Globals {
+    builtins: HashMap<String, LiteralValue>,
+    values: HashMap<String, LiteralValue>,
+}
+
+impl Globals {
+    pub fn new() -> Self {
+        let mut builtins = HashMap::new();
+        for native in natives::globals() {
+            builtins.insert(native.name.to_string(), LiteralValue::Native(native));
+        }
+        for namespace in natives::namespaces() {
+            builtins.insert(
+                namespace.name.clone(),
+                LiteralValue::Namespace(Rc::new(namespace)),
+            );
+        }
+        Globals {
+            builtins,
+            values: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<LiteralValue> {
+        self.values
+            .get(name)
+            .or_else(|| self.builtins.get(name))
+            .cloned()
+    }
+
+    pub fn define(&mut self, name: &str, value: LiteralValue) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    //False when no such variable exists
+    pub fn assign(&mut self, name: &str, value: LiteralValue) -> bool {
+        let slot = match self.values.get_mut(name) {
+            Some(slot) => slot,
+            None => match self.builtins.get_mut(name) {
+                Some(slot) => slot,
+                None => return false,
+            },
+        };
+        *slot = value;
+        true
+    }
+
+    //What importing the module gives, its variables without the natives
+    pub fn namespace(&self, name: &str) -> Namespace {
+        let mut namespace = Namespace::new(name);
+        for (name, value) in &self.values {
+            namespace.define(name, value.clone());
+        }
+        namespace
+    }
+}
+
+impl Default for Globals {
+    fn default() -> Self {
+        Self::new()
+    }
+}
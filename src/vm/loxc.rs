@@ -0,0 +1,945 @@
+use std::rc::Rc;
+
+use crate::{
+    scanner::{token::Token, token_type::TokenType},
+    utils::literal_value::LiteralValue,
+};
+
+use super::{
+    chunk::{Chunk, Constant},
+    function::{FunctionProto, Param},
+    opcode::OpCode,
+};
+
+/// How a .loxc file starts.
+pub const MAGIC: &[u8; 4] = b"LOXC";
+
+/// Layout of the file and meaning of the opcodes it holds. Bump it whenever
+/// either changes, files of another version are refused.
+pub const FORMAT_VERSION: u16 = 1;
+
+//Magic, u16 version and u32 checksum of everything after them
+const HEADER_BYTES: usize = 10;
+
+/// A compiled script as a .loxc file: the header, then the script function
+/// with its constants, code, lines and source tokens, nested functions
+/// inline where they are constants. Numbers are big endian.
+pub fn write(script: &FunctionProto) -> Vec<u8> {
+    let mut payload = Encoder { out: Vec::new() };
+    payload.function(script);
+    let mut out = Vec::with_capacity(HEADER_BYTES + payload.out.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&FORMAT_VERSION.to_be_bytes());
+    out.extend_from_slice(&checksum(&payload.out).to_be_bytes());
+    out.extend(payload.out);
+    out
+}
+
+/// The script of a .loxc file, after checking it is one, of this version,
+/// intact, and only holds code the VM can run.
+pub fn read(bytes: &[u8]) -> Result<FunctionProto, String> {
+    if bytes.len() < HEADER_BYTES || &bytes[..4] != MAGIC {
+        return Err("Not a compiled Lox file".to_string());
+    }
+    let version = u16::from_be_bytes([bytes[4], bytes[5]]);
+    if version != FORMAT_VERSION {
+        return Err(format!(
+            "Compiled for format version {}, this interpreter reads version {}",
+            version, FORMAT_VERSION
+        ));
+    }
+    let expected = u32::from_be_bytes([bytes[6], bytes[7], bytes[8], bytes[9]]);
+    let payload = &bytes[HEADER_BYTES..];
+    if checksum(payload) != expected {
+        return Err("Checksum mismatch, the file is corrupted".to_string());
+    }
+    let mut decoder = Decoder {
+        bytes: payload,
+        position: 0,
+    };
+    let script = decoder.function()?;
+    if decoder.position != payload.len() {
+        return Err("Unexpected bytes after the script".to_string());
+    }
+    Ok(script)
+}
+
+struct Encoder {
+    out: Vec<u8>,
+}
+
+impl Encoder {
+    fn u8(&mut self, value: u8) {
+        self.out.push(value);
+    }
+
+    fn u32(&mut self, value: usize) {
+        let value = u32::try_from(value).expect("compiled code stays far below 4GB");
+        self.out.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn string(&mut self, value: &str) {
+        self.u32(value.len());
+        self.out.extend_from_slice(value.as_bytes());
+    }
+
+    fn function(&mut self, function: &FunctionProto) {
+        match &function.name {
+            Some(name) => {
+                self.u8(1);
+                self.string(name);
+            }
+            None => self.u8(0),
+        }
+        self.u32(function.params.len());
+        for param in &function.params {
+            self.string(&param.name);
+            self.u8(param.default as u8 | (param.rest as u8) << 1);
+        }
+        self.u32(function.upvalues);
+        self.chunk(&function.chunk);
+    }
+
+    fn chunk(&mut self, chunk: &Chunk) {
+        self.u32(chunk.code.len());
+        self.out.extend_from_slice(&chunk.code);
+        //Lines as runs, most instructions share the line of the one before
+        let mut runs: Vec<(usize, usize)> = Vec::new();
+        for &line in &chunk.lines {
+            match runs.last_mut() {
+                Some((last, count)) if *last == line => *count += 1,
+                _ => runs.push((line, 1)),
+            }
+        }
+        self.u32(runs.len());
+        for (line, count) in runs {
+            self.u32(line);
+            self.u32(count);
+        }
+        self.u32(chunk.constants.len());
+        for constant in &chunk.constants {
+            match constant {
+                Constant::Value(value) => {
+                    self.u8(0);
+                    self.value(value);
+                }
+                Constant::Function(function) => {
+                    self.u8(1);
+                    self.function(function);
+                }
+                Constant::Names(names) => {
+                    self.u8(2);
+                    self.u32(names.len());
+                    for name in names.iter() {
+                        self.string(name);
+                    }
+                }
+            }
+        }
+        self.u32(chunk.sites.len());
+        for (offset, token) in &chunk.sites {
+            self.u32(*offset);
+            self.token(token);
+        }
+    }
+
+    //Source tokens keep what errors and operators read from them
+    fn token(&mut self, token: &Token) {
+        self.u8(token.token_type as u8);
+        self.string(&token.lexame);
+        self.u32(token.line);
+        self.value(&token.literal);
+    }
+
+    fn value(&mut self, value: &LiteralValue) {
+        match value {
+            LiteralValue::Nil => self.u8(0),
+            LiteralValue::Boolean(false) => self.u8(1),
+            LiteralValue::Boolean(true) => self.u8(2),
+            LiteralValue::Float(number) => {
+                self.u8(3);
+                self.out.extend_from_slice(&number.to_bits().to_be_bytes());
+            }
+            LiteralValue::String(text) => {
+                self.u8(4);
+                self.string(text);
+            }
+            LiteralValue::None => self.u8(5),
+            other => unreachable!("source code has no {} literals", other.type_name()),
+        }
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Decoder<'_> {
+    fn take(&mut self, count: usize) -> Result<&[u8], String> {
+        match self.bytes.get(self.position..self.position + count) {
+            Some(bytes) => {
+                self.position += count;
+                Ok(bytes)
+            }
+            None => Err("The file is truncated".to_string()),
+        }
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<usize, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.u32()?;
+        match String::from_utf8(self.take(len)?.to_vec()) {
+            Ok(text) => Ok(text),
+            Err(_) => Err("Invalid UTF-8 in a string".to_string()),
+        }
+    }
+
+    fn function(&mut self) -> Result<FunctionProto, String> {
+        let name = match self.u8()? {
+            0 => None,
+            1 => Some(self.string()?),
+            tag => return Err(format!("Invalid function name tag {}", tag)),
+        };
+        let mut params = Vec::new();
+        for _ in 0..self.u32()? {
+            let name = self.string()?;
+            let flags = self.u8()?;
+            params.push(Param {
+                name,
+                default: flags & 1 != 0,
+                rest: flags & 2 != 0,
+            });
+        }
+        let upvalues = self.u32()?;
+        let function = FunctionProto {
+            name,
+            params,
+            upvalues,
+            chunk: self.chunk()?,
+        };
+        verify(&function)?;
+        Ok(function)
+    }
+
+    fn chunk(&mut self) -> Result<Chunk, String> {
+        let mut chunk = Chunk::new();
+        let len = self.u32()?;
+        chunk.code = self.take(len)?.to_vec();
+        for _ in 0..self.u32()? {
+            let line = self.u32()?;
+            let count = self.u32()?;
+            if chunk.lines.len() + count > chunk.code.len() {
+                return Err("Line table longer than the code".to_string());
+            }
+            chunk.lines.extend(std::iter::repeat_n(line, count));
+        }
+        if chunk.lines.len() != chunk.code.len() {
+            return Err("Line table shorter than the code".to_string());
+        }
+        for _ in 0..self.u32()? {
+            let constant = match self.u8()? {
+                0 => Constant::Value(self.value()?),
+                1 => Constant::Function(Rc::new(self.function()?)),
+                2 => {
+                    let mut names = Vec::new();
+                    for _ in 0..self.u32()? {
+                        names.push(self.string()?);
+                    }
+                    Constant::Names(names.into())
+                }
+                tag => return Err(format!("Invalid constant tag {}", tag)),
+            };
+            chunk.constants.push(constant);
+        }
+        for _ in 0..self.u32()? {
+            let offset = self.u32()?;
+            let token = self.token()?;
+            if chunk.sites.last().is_some_and(|(last, _)| *last >= offset) {
+                return Err("Source tokens out of order".to_string());
+            }
+            chunk.add_site(offset, token);
+        }
+        Ok(chunk)
+    }
+
+    fn token(&mut self) -> Result<Token, String> {
+        let kind = self.u8()?;
+        let token_type = match TokenType::from_byte(kind) {
+            Some(token_type) => token_type,
+            None => return Err(format!("Invalid token type {}", kind)),
+        };
+        let lexame = self.string()?;
+        let line = self.u32()?;
+        Ok(Token::new(token_type, lexame, line, self.value()?))
+    }
+
+    fn value(&mut self) -> Result<LiteralValue, String> {
+        Ok(match self.u8()? {
+            0 => LiteralValue::Nil,
+            1 => LiteralValue::Boolean(false),
+            2 => LiteralValue::Boolean(true),
+            3 => {
+                let bytes = self.take(8)?;
+                let bits = u64::from_be_bytes(bytes.try_into().expect("took 8 bytes"));
+                LiteralValue::Float(f64::from_bits(bits))
+            }
+            4 => LiteralValue::String(self.string()?),
+            5 => LiteralValue::None,
+            tag => return Err(format!("Invalid value tag {}", tag)),
+        })
+    }
+}
+
+//Checks the code of a function can't make the VM panic: every instruction
+//decodes and refers to constants of the kind it expects, every path through
+//it keeps the stack deep enough for the locals it reads and what it pops,
+//and nothing depends on a source token, upvalue or caught value that isn't
+//there. Functions nested as constants were checked when they were decoded
+fn verify(function: &FunctionProto) -> Result<(), String> {
+    let starts = decode(function)?;
+    let chunk = &function.chunk;
+    let invalid = |offset: usize, what: &str| {
+        format!(
+            "Invalid bytecode in {} at {}: {}",
+            function.display_name(),
+            offset,
+            what
+        )
+    };
+    //The callee and the arguments it was called with
+    let entry = State {
+        stack: vec![Slot::VALUE; 1 + function.params.len()],
+        handlers: Vec::new(),
+    };
+    let mut states: Vec<Option<State>> = vec![None; chunk.code.len()];
+    let mut pending = Vec::new();
+    flow_into(&mut states, &mut pending, &starts, 0, entry).map_err(|what| invalid(0, what))?;
+    while let Some(offset) = pending.pop() {
+        let state = states[offset]
+            .clone()
+            .expect("pending offsets have a state");
+        for (target, state) in
+            successors(function, offset, state).map_err(|what| invalid(offset, &what))?
+        {
+            flow_into(&mut states, &mut pending, &starts, target, state)
+                .map_err(|what| invalid(offset, what))?;
+        }
+    }
+    Ok(())
+}
+
+//Checks every instruction decodes, its operands stay inside the code, jumps
+//land inside it and constants are of the kind it expects. Enough for the
+//disassembler, which follows the code in order. Gives where instructions start
+fn decode(function: &FunctionProto) -> Result<Vec<bool>, String> {
+    let chunk = &function.chunk;
+    let invalid = |offset: usize, what: &str| {
+        Err(format!(
+            "Invalid bytecode in {} at {}: {}",
+            function.display_name(),
+            offset,
+            what
+        ))
+    };
+    let mut starts = vec![false; chunk.code.len()];
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        starts[offset] = true;
+        let op = match OpCode::from_byte(chunk.code[offset]) {
+            Some(op) => op,
+            None => return invalid(offset, "unknown opcode"),
+        };
+        let next = offset + 1 + op.operand_bytes();
+        if next > chunk.code.len() {
+            return invalid(offset, "operands run past the end");
+        }
+        let short = |index: usize| chunk.read_u16(offset + 1 + index) as usize;
+        let constant = |index: usize| chunk.constants.get(short(index));
+        let mut end = next;
+        let fits = match op {
+            OpCode::Constant => matches!(constant(0), Some(Constant::Value(_))),
+            OpCode::GetGlobal
+            | OpCode::SetGlobal
+            | OpCode::DefineGlobal
+            | OpCode::GetMember
+            | OpCode::Import
+            | OpCode::ImportName => {
+                matches!(constant(0), Some(Constant::Value(LiteralValue::String(_))))
+            }
+            OpCode::CallNamed | OpCode::TailCallNamed => {
+                matches!(constant(1), Some(Constant::Names(_)))
+            }
+            OpCode::Closure => match constant(0) {
+                Some(Constant::Function(inner)) => {
+                    end += inner.upvalues * 2;
+                    end <= chunk.code.len()
+                }
+                _ => false,
+            },
+            OpCode::Jump
+            | OpCode::JumpIfFalse
+            | OpCode::JumpIfTrue
+            | OpCode::IterNext
+            | OpCode::PushHandler => next + short(0) <= chunk.code.len(),
+            OpCode::JumpIfBound => next + short(1) <= chunk.code.len(),
+            OpCode::Loop => short(0) <= next,
+            _ => true,
+        };
+        if !fits {
+            return invalid(offset, &format!("bad operands for {:?}", op));
+        }
+        offset = end;
+    }
+    Ok(starts)
+}
+
+//What the verifier knows of a stack slot where an instruction starts
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Slot {
+    kind: Kind,
+    //Captured by a closure made in this frame, only CloseUpvalue may pop it
+    captured: bool,
+    //The frame keeps what was thrown to put there, which Rethrow needs
+    caught: bool,
+}
+
+//The values some instructions need underneath them
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Value,
+    //Made by IterStart, for IterNext
+    Iterator,
+    //Made by Import, for ImportName
+    Namespace,
+}
+
+impl Slot {
+    const VALUE: Slot = Slot {
+        kind: Kind::Value,
+        captured: false,
+        caught: false,
+    };
+
+    fn of(kind: Kind) -> Slot {
+        Slot {
+            kind,
+            ..Slot::VALUE
+        }
+    }
+
+    //What holds on both paths reaching an instruction
+    fn join(self, other: Slot) -> Slot {
+        Slot {
+            kind: match self.kind == other.kind {
+                true => self.kind,
+                false => Kind::Value,
+            },
+            captured: self.captured || other.captured,
+            caught: self.caught && other.caught,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct State {
+    stack: Vec<Slot>,
+    //Height of the stack and target of each handler pushed in the frame
+    handlers: Vec<(usize, usize)>,
+}
+
+//Joins `state` into what is known at `target`, queueing it when that changed.
+//Paths may only meet with the same stack height and handlers
+fn flow_into(
+    states: &mut [Option<State>],
+    pending: &mut Vec<usize>,
+    starts: &[bool],
+    target: usize,
+    state: State,
+) -> Result<(), &'static str> {
+    if !starts.get(target).copied().unwrap_or(false) {
+        return Err("runs off the code or into the middle of an instruction");
+    }
+    let joined = match &states[target] {
+        None => state,
+        Some(known) => {
+            if known.stack.len() != state.stack.len() || known.handlers != state.handlers {
+                return Err("paths meet with different stack heights or handlers");
+            }
+            let stack = known
+                .stack
+                .iter()
+                .zip(&state.stack)
+                .map(|(known, slot)| known.join(*slot))
+                .collect();
+            State {
+                stack,
+                handlers: state.handlers,
+            }
+        }
+    };
+    if states[target].as_ref() != Some(&joined) {
+        states[target] = Some(joined);
+        pending.push(target);
+    }
+    Ok(())
+}
+
+//Where the instruction at `offset` goes on and with what stack, including
+//the handler anything thrown there lands in
+fn successors(
+    function: &FunctionProto,
+    offset: usize,
+    mut state: State,
+) -> Result<Vec<(usize, State)>, String> {
+    let chunk = &function.chunk;
+    let op = OpCode::from_byte(chunk.code[offset]).expect("decoded already");
+    let next = offset + 1 + op.operand_bytes();
+    let byte = |index: usize| chunk.code[offset + 1 + index] as usize;
+    let short = |index: usize| chunk.read_u16(offset + 1 + index) as usize;
+    let site = chunk
+        .sites
+        .binary_search_by_key(&offset, |(site, _)| *site)
+        .map(|index| &chunk.sites[index].1);
+    if op.needs_site() && site.is_err() {
+        return Err(format!("no source token for {:?}", op));
+    }
+    if let (Some(operator), Ok(token)) = (op.operator(), site) {
+        if token.token_type != operator {
+            return Err(format!(
+                "{:?} has a {:?} source token",
+                op, token.token_type
+            ));
+        }
+    }
+    let mut successors = Vec::new();
+    //Where it goes on when it doesn't jump, None after the last of a path
+    let mut fallthrough = Some(next);
+
+    //The stack is cut back to the handler's height and the thrown value
+    //pushed, the frame keeping it for a Rethrow
+    if let Some(&(height, target)) = state.handlers.last() {
+        let mut landed = State {
+            stack: state.stack[..height].to_vec(),
+            handlers: state.handlers[..state.handlers.len() - 1].to_vec(),
+        };
+        if op == OpCode::Rethrow && byte(0) < height {
+            landed.stack[byte(0)].caught = false;
+        }
+        landed.stack.push(Slot {
+            caught: true,
+            ..Slot::VALUE
+        });
+        successors.push((target, landed));
+    }
+
+    let height = state.stack.len();
+    let names = |index: usize| match &chunk.constants[short(index)] {
+        Constant::Names(names) => names.len(),
+        _ => unreachable!("decoded already"),
+    };
+    let pops = match op {
+        OpCode::Constant
+        | OpCode::Nil
+        | OpCode::True
+        | OpCode::False
+        | OpCode::GetLocal
+        | OpCode::GetUpvalue
+        | OpCode::GetGlobal
+        | OpCode::Closure
+        | OpCode::Import
+        | OpCode::Jump
+        | OpCode::Loop
+        | OpCode::JumpIfBound
+        | OpCode::PushHandler
+        | OpCode::PopHandler
+        | OpCode::Rethrow => 0,
+        OpCode::Add
+        | OpCode::Subtract
+        | OpCode::Multiply
+        | OpCode::Divide
+        | OpCode::Modulo
+        | OpCode::FloorDivide
+        | OpCode::Power
+        | OpCode::Equal
+        | OpCode::NotEqual
+        | OpCode::Greater
+        | OpCode::GreaterEqual
+        | OpCode::Less
+        | OpCode::LessEqual
+        | OpCode::Index => 2,
+        OpCode::SetIndex => 3,
+        OpCode::List | OpCode::Interpolate => short(0),
+        OpCode::Map => short(0) * 2,
+        OpCode::Call | OpCode::TailCall => byte(0) + 1,
+        OpCode::CallNamed | OpCode::TailCallNamed => byte(0) + names(1) + 1,
+        //The rest pop or look at the value on top
+        _ => 1,
+    };
+    //Below the floor are the callee, or what a handler cuts the stack back to
+    let floor = state.handlers.last().map_or(1, |(height, _)| *height);
+    if height < floor + pops {
+        return Err(format!(
+            "{:?} pops below the start of its frame or handler",
+            op
+        ));
+    }
+    let popped = state.stack.split_off(height - pops);
+    if op != OpCode::CloseUpvalue && popped.iter().any(|slot| slot.captured) {
+        return Err(format!("{:?} pops a captured local", op));
+    }
+    let top = popped.last().copied();
+    let local = |state: &State, slot: usize| match state.stack.get(slot) {
+        Some(slot) => Ok(*slot),
+        None => Err(format!(
+            "{:?} uses local {} of {}",
+            op,
+            slot,
+            state.stack.len()
+        )),
+    };
+    let upvalue = |index: usize| match index < function.upvalues {
+        true => Ok(()),
+        false => Err(format!(
+            "{:?} uses upvalue {} of {}",
+            op, index, function.upvalues
+        )),
+    };
+
+    match op {
+        OpCode::GetLocal => {
+            let kind = local(&state, byte(0))?.kind;
+            state.stack.push(Slot::of(kind));
+        }
+        OpCode::SetLocal => {
+            let top = top.expect("SetLocal looks at one");
+            local(&state, byte(0))?;
+            let slot = &mut state.stack[byte(0)];
+            if !slot.captured {
+                slot.kind = top.kind;
+            }
+            state.stack.push(top);
+        }
+        OpCode::GetUpvalue => {
+            upvalue(byte(0))?;
+            state.stack.push(Slot::VALUE);
+        }
+        OpCode::SetUpvalue => {
+            upvalue(byte(0))?;
+            state.stack.extend(top);
+        }
+        OpCode::JumpIfBound => {
+            local(&state, byte(0))?;
+            successors.push((next + short(1), state.clone()));
+        }
+        OpCode::JumpIfFalse | OpCode::JumpIfTrue => {
+            state.stack.extend(top);
+            successors.push((next + short(0), state.clone()));
+        }
+        OpCode::Jump => fallthrough = Some(next + short(0)),
+        OpCode::Loop => fallthrough = Some(next - short(0)),
+        OpCode::Closure => {
+            let inner = match &chunk.constants[short(0)] {
+                Constant::Function(inner) => inner,
+                _ => unreachable!("decoded already"),
+            };
+            for index in 0..inner.upvalues {
+                let slot = byte(3 + index * 2);
+                match byte(2 + index * 2) {
+                    0 => upvalue(slot)?,
+                    1 => match state.stack.get_mut(slot) {
+                        //What a closure sets it to is anyone's guess
+                        Some(captured) => {
+                            *captured = Slot {
+                                kind: Kind::Value,
+                                captured: true,
+                                ..*captured
+                            }
+                        }
+                        None => {
+                            return Err(format!("Closure captures local {} of {}", slot, height))
+                        }
+                    },
+                    _ => return Err("Closure upvalue is neither local nor enclosing".to_string()),
+                }
+            }
+            state.stack.push(Slot::VALUE);
+            fallthrough = Some(next + inner.upvalues * 2);
+        }
+        OpCode::Import => {
+            match site {
+                Ok(token) if matches!(token.literal, LiteralValue::String(_)) => {}
+                _ => return Err("Import without a module path".to_string()),
+            }
+            state.stack.push(Slot::of(Kind::Namespace));
+        }
+        OpCode::ImportName => {
+            let top = top.expect("ImportName looks at one");
+            if top.kind != Kind::Namespace {
+                return Err("ImportName without a module under it".to_string());
+            }
+            state.stack.extend([top, Slot::VALUE]);
+        }
+        OpCode::IterStart => state.stack.push(Slot::of(Kind::Iterator)),
+        OpCode::IterNext => {
+            let top = top.expect("IterNext looks at one");
+            if top.kind != Kind::Iterator {
+                return Err("IterNext without an iterator under it".to_string());
+            }
+            state.stack.push(top);
+            successors.push((next + short(0), state.clone()));
+            state.stack.push(Slot::VALUE);
+        }
+        OpCode::PushHandler => state.handlers.push((height, next + short(0))),
+        OpCode::PopHandler => {
+            if state.handlers.pop().is_none() {
+                return Err("PopHandler without a handler".to_string());
+            }
+        }
+        OpCode::TailCall | OpCode::TailCallNamed if !state.handlers.is_empty() => {
+            return Err(format!("{:?} inside a handler", op));
+        }
+        OpCode::Rethrow => {
+            if !local(&state, byte(0))?.caught {
+                return Err(format!(
+                    "Rethrow of local {}, which holds nothing caught",
+                    byte(0)
+                ));
+            }
+            fallthrough = None;
+        }
+        OpCode::Return | OpCode::TailCall | OpCode::TailCallNamed | OpCode::Throw => {
+            fallthrough = None;
+        }
+        OpCode::Pop | OpCode::Print | OpCode::DefineGlobal | OpCode::CloseUpvalue => {}
+        _ => state.stack.push(Slot::VALUE),
+    }
+    successors.extend(fallthrough.map(|target| (target, state)));
+    Ok(successors)
+}
+
+//CRC-32 as in zip and PNG
+fn checksum(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        parser::parser::Parser,
+        scanner::scanner::Scanner,
+        vm::{compiler, disassembler},
+    };
+
+    const SOURCE: &str = "
+fun counter(step = 1) {
+  var n = 0;
+  fun inc() { n = n + step; return n; }
+  return inc;
+}
+var c = counter(step: 2);
+for (x in [1, 2]) {
+  try { if (x == 2) throw error(\"two\"); print c(); }
+  catch (e) { print e.message; }
+  finally { print \"${x} done\"; }
+}
+{ from \"m.lox\" import a, b; }
+";
+
+    fn compile(source: &str) -> FunctionProto {
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let statements = Parser::new(tokens).parse().expect("the source parses");
+        compiler::compile(&statements).expect("the source compiles")
+    }
+
+    //The file of a payload, checksum and all
+    fn file(payload: &[u8]) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&FORMAT_VERSION.to_be_bytes());
+        bytes.extend_from_slice(&checksum(payload).to_be_bytes());
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    //A script running `code`, with the site of an instruction at `site`
+    fn script(code: &[u8], site: Option<usize>) -> FunctionProto {
+        let mut chunk = Chunk::new();
+        for &byte in code {
+            chunk.write(byte, 1);
+        }
+        if let Some(offset) = site {
+            chunk.add_site(
+                offset,
+                Token::new(TokenType::Plus, "+".to_string(), 1, LiteralValue::Nil),
+            );
+        }
+        FunctionProto {
+            name: None,
+            params: Vec::new(),
+            upvalues: 0,
+            chunk,
+        }
+    }
+
+    fn read_error(bytes: &[u8]) -> String {
+        read(bytes).expect_err("the file is refused")
+    }
+
+    #[test]
+    fn written_scripts_read_back_the_same() {
+        let script = compile(SOURCE);
+        let bytes = write(&script);
+        let read = read(&bytes).expect("the file reads back");
+        assert_eq!(
+            disassembler::disassemble(&read, "<script>"),
+            disassembler::disassemble(&script, "<script>")
+        );
+        assert_eq!(write(&read), bytes);
+    }
+
+    #[test]
+    fn every_test_program_reads_back() {
+        let directory = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+        for entry in std::fs::read_dir(directory).expect("tests/programs exists") {
+            let path = entry.expect("readable directory entry").path();
+            if path.extension().is_some_and(|extension| extension == "lox") {
+                let source = std::fs::read_to_string(&path).expect("readable program");
+                let script = compile(&source);
+                if let Err(message) = read(&write(&script)) {
+                    panic!("{}: {}", path.display(), message);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn damaged_files_are_refused() {
+        let bytes = write(&compile(SOURCE));
+
+        let mut flipped = bytes.clone();
+        flipped[HEADER_BYTES + 20] ^= 1;
+        assert_eq!(
+            read_error(&flipped),
+            "Checksum mismatch, the file is corrupted"
+        );
+
+        let mut magic = bytes.clone();
+        magic[0] = b'X';
+        assert_eq!(read_error(&magic), "Not a compiled Lox file");
+        assert_eq!(
+            read_error(&bytes[..HEADER_BYTES - 1]),
+            "Not a compiled Lox file"
+        );
+
+        let mut version = bytes.clone();
+        version[5] += 1;
+        assert!(read_error(&version).starts_with("Compiled for format version 2"));
+
+        let payload = &bytes[HEADER_BYTES..];
+        assert_eq!(
+            read_error(&bytes[..bytes.len() - 1]),
+            "Checksum mismatch, the file is corrupted"
+        );
+        assert_eq!(
+            read_error(&file(&payload[..payload.len() - 1])),
+            "The file is truncated"
+        );
+        let mut longer = payload.to_vec();
+        longer.push(0);
+        assert_eq!(
+            read_error(&file(&longer)),
+            "Unexpected bytes after the script"
+        );
+    }
+
+    #[test]
+    fn locals_beyond_the_stack_are_refused() {
+        let get = script(&[OpCode::GetLocal as u8, 200, OpCode::Return as u8], None);
+        assert_eq!(
+            read_error(&write(&get)),
+            "Invalid bytecode in <lambda> at 0: GetLocal uses local 200 of 1"
+        );
+        let upvalue = script(&[OpCode::GetUpvalue as u8, 0, OpCode::Return as u8], None);
+        assert_eq!(
+            read_error(&write(&upvalue)),
+            "Invalid bytecode in <lambda> at 0: GetUpvalue uses upvalue 0 of 0"
+        );
+        let underflow = script(
+            &[OpCode::Pop as u8, OpCode::Nil as u8, OpCode::Return as u8],
+            None,
+        );
+        assert_eq!(
+            read_error(&write(&underflow)),
+            "Invalid bytecode in <lambda> at 0: Pop pops below the start of its frame or handler"
+        );
+    }
+
+    #[test]
+    fn instructions_reporting_errors_need_their_token() {
+        let add = [
+            OpCode::Nil as u8,
+            OpCode::Nil as u8,
+            OpCode::Add as u8,
+            OpCode::Return as u8,
+        ];
+        assert!(read(&write(&script(&add, Some(2)))).is_ok());
+        assert_eq!(
+            read_error(&write(&script(&add, None))),
+            "Invalid bytecode in <lambda> at 2: no source token for Add"
+        );
+    }
+
+    #[test]
+    fn operator_tokens_must_match_their_instruction() {
+        let multiply = [
+            OpCode::Nil as u8,
+            OpCode::Nil as u8,
+            OpCode::Multiply as u8,
+            OpCode::Return as u8,
+        ];
+        assert_eq!(
+            read_error(&write(&script(&multiply, Some(2)))),
+            "Invalid bytecode in <lambda> at 2: Multiply has a Plus source token"
+        );
+        let negate = [
+            OpCode::Nil as u8,
+            OpCode::Negate as u8,
+            OpCode::Return as u8,
+        ];
+        assert_eq!(
+            read_error(&write(&script(&negate, Some(1)))),
+            "Invalid bytecode in <lambda> at 1: Negate has a Plus source token"
+        );
+    }
+
+    #[test]
+    fn paths_must_end_in_a_return_and_rethrow_what_they_caught() {
+        let falls_off = script(&[OpCode::Nil as u8, OpCode::Pop as u8], None);
+        assert_eq!(
+            read_error(&write(&falls_off)),
+            "Invalid bytecode in <lambda> at 1: runs off the code or into the middle of an instruction"
+        );
+        let rethrow = script(&[OpCode::Nil as u8, OpCode::Rethrow as u8, 1], None);
+        assert_eq!(
+            read_error(&write(&rethrow)),
+            "Invalid bytecode in <lambda> at 1: Rethrow of local 1, which holds nothing caught"
+        );
+    }
+}
@@ -0,0 +1,240 @@
+use crate::scanner::token_type::TokenType;
+
+/// One instruction of a chunk, stored as a byte and followed by its
+/// operands. u16 operands are big endian, jump offsets count from the end
+/// of the instruction.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OpCode {
+    //u16 constant
+    Constant,
+    Nil,
+    True,
+    False,
+    Pop,
+    //u8 slot in the frame
+    GetLocal,
+    SetLocal,
+    //u8 index in the closure
+    GetUpvalue,
+    SetUpvalue,
+    //u16 constant naming the global
+    GetGlobal,
+    SetGlobal,
+    DefineGlobal,
+    //Moves the local on top of the stack into the closures capturing it
+    CloseUpvalue,
+    Negate,
+    Not,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    FloorDivide,
+    Power,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Print,
+    //u16 forward offset
+    Jump,
+    //u16 forward offset, the condition stays on the stack
+    JumpIfFalse,
+    JumpIfTrue,
+    //u16 backward offset
+    Loop,
+    //u8 slot, u16 forward offset: skips a default the caller gave a value for
+    JumpIfBound,
+    //u8 positional arguments
+    Call,
+    //u8 positional arguments, u16 constant with the names of the named ones
+    CallNamed,
+    //Calls in tail position, which replace the frame making them
+    TailCall,
+    TailCallNamed,
+    //u16 function constant, then u8 is_local and u8 index for each upvalue
+    Closure,
+    Return,
+    //u16 element count
+    List,
+    //u16 entry count
+    Map,
+    Index,
+    SetIndex,
+    //u16 constant naming the member
+    GetMember,
+    //u16 part count
+    Interpolate,
+    //Turns the value on top into an iterator
+    IterStart,
+    //u16 forward offset taken when the iterator below is done
+    IterNext,
+    //u16 forward offset to the handler
+    PushHandler,
+    PopHandler,
+    Throw,
+    //u8 slot holding a caught value, thrown again as it was caught
+    Rethrow,
+    //u16 constant with the module path
+    Import,
+    //u16 constant naming the export of the module on top
+    ImportName,
+}
+
+const OPCODES: [OpCode; 54] = [
+    OpCode::Constant,
+    OpCode::Nil,
+    OpCode::True,
+    OpCode::False,
+    OpCode::Pop,
+    OpCode::GetLocal,
+    OpCode::SetLocal,
+    OpCode::GetUpvalue,
+    OpCode::SetUpvalue,
+    OpCode::GetGlobal,
+    OpCode::SetGlobal,
+    OpCode::DefineGlobal,
+    OpCode::CloseUpvalue,
+    OpCode::Negate,
+    OpCode::Not,
+    OpCode::Add,
+    OpCode::Subtract,
+    OpCode::Multiply,
+    OpCode::Divide,
+    OpCode::Modulo,
+    OpCode::FloorDivide,
+    OpCode::Power,
+    OpCode::Equal,
+    OpCode::NotEqual,
+    OpCode::Greater,
+    OpCode::GreaterEqual,
+    OpCode::Less,
+    OpCode::LessEqual,
+    OpCode::Print,
+    OpCode::Jump,
+    OpCode::JumpIfFalse,
+    OpCode::JumpIfTrue,
+    OpCode::Loop,
+    OpCode::JumpIfBound,
+    OpCode::Call,
+    OpCode::CallNamed,
+    OpCode::TailCall,
+    OpCode::TailCallNamed,
+    OpCode::Closure,
+    OpCode::Return,
+    OpCode::List,
+    OpCode::Map,
+    OpCode::Index,
+    OpCode::SetIndex,
+    OpCode::GetMember,
+    OpCode::Interpolate,
+    OpCode::IterStart,
+    OpCode::IterNext,
+    OpCode::PushHandler,
+    OpCode::PopHandler,
+    OpCode::Throw,
+    OpCode::Rethrow,
+    OpCode::Import,
+    OpCode::ImportName,
+];
+
+impl OpCode {
+    pub fn from_byte(byte: u8) -> Option<OpCode> {
+        OPCODES.get(byte as usize).copied()
+    }
+
+    //Bytes of operands after the opcode, not counting the upvalues of Closure
+    pub fn operand_bytes(self) -> usize {
+        match self {
+            OpCode::GetLocal
+            | OpCode::SetLocal
+            | OpCode::GetUpvalue
+            | OpCode::SetUpvalue
+            | OpCode::Call
+            | OpCode::TailCall
+            | OpCode::Rethrow => 1,
+            OpCode::Constant
+            | OpCode::GetGlobal
+            | OpCode::SetGlobal
+            | OpCode::DefineGlobal
+            | OpCode::Jump
+            | OpCode::JumpIfFalse
+            | OpCode::JumpIfTrue
+            | OpCode::Loop
+            | OpCode::Closure
+            | OpCode::List
+            | OpCode::Map
+            | OpCode::GetMember
+            | OpCode::Interpolate
+            | OpCode::IterNext
+            | OpCode::PushHandler
+            | OpCode::Import
+            | OpCode::ImportName => 2,
+            OpCode::JumpIfBound | OpCode::CallNamed | OpCode::TailCallNamed => 3,
+            _ => 0,
+        }
+    }
+
+    //The operator an arithmetic or comparison instruction applies, its site
+    //has to be a token of this type
+    pub fn operator(self) -> Option<TokenType> {
+        match self {
+            OpCode::Negate | OpCode::Subtract => Some(TokenType::Minus),
+            OpCode::Not => Some(TokenType::Bang),
+            OpCode::Add => Some(TokenType::Plus),
+            OpCode::Multiply => Some(TokenType::Star),
+            OpCode::Divide => Some(TokenType::Slash),
+            OpCode::Modulo => Some(TokenType::Percent),
+            OpCode::FloorDivide => Some(TokenType::TildeSlash),
+            OpCode::Power => Some(TokenType::StarStar),
+            OpCode::Equal => Some(TokenType::EqualEqual),
+            OpCode::NotEqual => Some(TokenType::BangEqual),
+            OpCode::Greater => Some(TokenType::Greater),
+            OpCode::GreaterEqual => Some(TokenType::GreaterEqual),
+            OpCode::Less => Some(TokenType::Less),
+            OpCode::LessEqual => Some(TokenType::LessEqual),
+            _ => None,
+        }
+    }
+
+    //Whether running the instruction can read the source token recorded for
+    //it, to report an error or, for Import, to find the module path
+    pub fn needs_site(self) -> bool {
+        matches!(
+            self,
+            OpCode::GetGlobal
+                | OpCode::SetGlobal
+                | OpCode::Negate
+                | OpCode::Not
+                | OpCode::Add
+                | OpCode::Subtract
+                | OpCode::Multiply
+                | OpCode::Divide
+                | OpCode::Modulo
+                | OpCode::FloorDivide
+                | OpCode::Power
+                | OpCode::Equal
+                | OpCode::NotEqual
+                | OpCode::Greater
+                | OpCode::GreaterEqual
+                | OpCode::Less
+                | OpCode::LessEqual
+                | OpCode::Call
+                | OpCode::CallNamed
+                | OpCode::TailCall
+                | OpCode::TailCallNamed
+                | OpCode::Map
+                | OpCode::Index
+                | OpCode::SetIndex
+                | OpCode::GetMember
+                | OpCode::IterStart
+                | OpCode::Throw
+                | OpCode::Import
+                | OpCode::ImportName
+        )
+    }
+}
@@ -0,0 +1,861 @@
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{
+    interpreter::{
+        call_frame::CallFrame,
+        control_flow::Thrown,
+        interpreter::DEFAULT_MAX_DEPTH,
+        iterator::LoxIterator,
+        lox_function,
+        modules::{self, ModuleLoader},
+        operations,
+        runtime_error::RuntimeError,
+    },
+    scanner::{token::Token, token_type::TokenType},
+    utils::{
+        literal_value::LiteralValue,
+        lox_error::{ErrorKind, LoxError},
+        lox_map::{LoxMap, MapKey},
+        native_function::NativeContext,
+        random::Random,
+    },
+};
+
+use super::{
+    chunk::{Chunk, Constant},
+    compiler, disassembler,
+    function::{Closure, FunctionProto, Module, Upvalue},
+    opcode::OpCode,
+};
+
+/// Runs compiled functions on a value stack. It is a second backend for the
+/// same language as `Interpreter`, configured the same way, and reports
+/// errors the same way. Calls don't nest on the Rust stack.
+pub struct Vm {
+    stack: Vec<LiteralValue>,
+    frames: Vec<Frame>,
+    handlers: Vec<Handler>,
+    //Upvalues still pointing into the stack, by increasing slot
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    checked_division: bool,
+    natives: NativeContext,
+    modules: ModuleLoader,
+    script: Option<PathBuf>,
+    max_depth: usize,
+    //Print the stack and the instruction before running each one
+    trace: bool,
+}
+
+struct Frame {
+    closure: Rc<Closure>,
+    ip: usize,
+    //Stack slot of the function being run, its locals follow
+    base: usize,
+    //The function that called it and the offset of the call, None for the script
+    caller: Option<(Rc<Closure>, usize)>,
+    //Running a module body, which returns the module's namespace
+    module: bool,
+    //Values its handlers caught by the slot they went to, with where they
+    //came from for when a finally throws them again
+    caught: Vec<(usize, Box<Thrown>)>,
+}
+
+//Where to go when a throw reaches it
+struct Handler {
+    frame: usize,
+    stack: usize,
+    target: usize,
+}
+
+//What the instruction just run asks of the dispatch loop
+enum Step {
+    Next,
+    Done,
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        Vm {
+            stack: Vec::new(),
+            frames: Vec::new(),
+            handlers: Vec::new(),
+            open_upvalues: Vec::new(),
+            checked_division: true,
+            natives: NativeContext::new(),
+            modules: ModuleLoader::new(),
+            script: None,
+            max_depth: DEFAULT_MAX_DEPTH,
+            trace: false,
+        }
+    }
+
+    /// Same as `Interpreter::set_checked_division`.
+    pub fn set_checked_division(&mut self, checked: bool) {
+        self.checked_division = checked;
+    }
+
+    /// Same as `Interpreter::set_seed`.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.natives.random = Random::new(seed);
+    }
+
+    /// Same as `Interpreter::set_filesystem`.
+    pub fn set_filesystem(&mut self, allowed: bool) {
+        self.natives.filesystem = allowed;
+    }
+
+    /// Same as `Interpreter::set_script`.
+    pub fn set_script(&mut self, path: &Path) {
+        self.script = Some(path.to_path_buf());
+    }
+
+    /// Same as `Interpreter::set_max_depth`.
+    pub fn set_max_depth(&mut self, depth: usize) {
+        self.max_depth = depth;
+    }
+
+    /// Prints the value stack and the instruction about to run to stderr
+    /// before every instruction, apart from the program's own output.
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
+    /// Runs a compiled script. An uncaught throw stops it and comes back
+    /// with the calls it went through.
+    pub fn run(&mut self, script: Rc<FunctionProto>) -> Result<(), Box<RuntimeError>> {
+        let closure = Rc::new(Closure {
+            function: script,
+            upvalues: Vec::new(),
+            module: Rc::new(Module::new(self.script.clone())),
+        });
+        self.stack.push(LiteralValue::Closure(closure.clone()));
+        self.frames.push(Frame {
+            closure,
+            ip: 0,
+            base: 0,
+            caller: None,
+            module: false,
+            caught: Vec::new(),
+        });
+        loop {
+            match self.step() {
+                Ok(Step::Next) => {}
+                Ok(Step::Done) => return Ok(()),
+                Err(thrown) => self.throw(thrown)?,
+            }
+        }
+    }
+
+    //The slots holding the script and the modules being run, by how the
+    //trace names them
+    fn frame_labels(&self) -> Vec<(usize, String)> {
+        let mut labels = Vec::new();
+        for frame in &self.frames {
+            match (&frame.caller, &frame.closure.module.path) {
+                (None, _) => labels.push((frame.base, "<script>".to_string())),
+                (Some(_), Some(path)) if frame.module => {
+                    let name = modules::module_name(path);
+                    labels.push((frame.base, format!("<module {}>", name)));
+                }
+                _ => {}
+            }
+        }
+        labels
+    }
+
+    fn frame(&self) -> &Frame {
+        self.frames.last().expect("a frame is always running")
+    }
+
+    fn frame_mut(&mut self) -> &mut Frame {
+        self.frames.last_mut().expect("a frame is always running")
+    }
+
+    fn pop(&mut self) -> LiteralValue {
+        self.stack.pop().expect("the compiler balances the stack")
+    }
+
+    fn peek(&self, distance: usize) -> &LiteralValue {
+        &self.stack[self.stack.len() - 1 - distance]
+    }
+
+    //Runs the instruction at the current frame's ip
+    fn step(&mut self) -> Result<Step, Box<Thrown>> {
+        let closure = self.frame().closure.clone();
+        let chunk = &closure.function.chunk;
+        let start = self.frame().ip;
+        if self.trace {
+            let stack = disassembler::stack(&self.stack, &self.frame_labels());
+            eprintln!("          {}", stack);
+            eprintln!("{:04} {}", start, disassembler::instruction(chunk, start));
+        }
+        let op = OpCode::from_byte(chunk.code[start]).expect("the compiler writes valid opcodes");
+        self.frame_mut().ip = start + 1 + op.operand_bytes();
+        let byte = |index: usize| chunk.code[start + 1 + index];
+        let short = |index: usize| chunk.read_u16(start + 1 + index) as usize;
+
+        match op {
+            OpCode::Constant => {
+                let value = match &chunk.constants[short(0)] {
+                    Constant::Value(value) => value.clone(),
+                    _ => unreachable!("Constant only loads values"),
+                };
+                self.stack.push(value);
+            }
+            OpCode::Nil => self.stack.push(LiteralValue::Nil),
+            OpCode::True => self.stack.push(LiteralValue::Boolean(true)),
+            OpCode::False => self.stack.push(LiteralValue::Boolean(false)),
+            OpCode::Pop => {
+                self.pop();
+            }
+            OpCode::GetLocal => {
+                let slot = self.frame().base + byte(0) as usize;
+                self.stack.push(self.stack[slot].clone());
+            }
+            OpCode::SetLocal => {
+                let slot = self.frame().base + byte(0) as usize;
+                self.stack[slot] = self.peek(0).clone();
+            }
+            OpCode::GetUpvalue => {
+                let value = match &*closure.upvalues[byte(0) as usize].borrow() {
+                    Upvalue::Open(slot) => self.stack[*slot].clone(),
+                    Upvalue::Closed(value) => value.clone(),
+                };
+                self.stack.push(value);
+            }
+            OpCode::SetUpvalue => {
+                let value = self.peek(0).clone();
+                match &mut *closure.upvalues[byte(0) as usize].borrow_mut() {
+                    Upvalue::Open(slot) => self.stack[*slot] = value,
+                    Upvalue::Closed(closed) => *closed = value,
+                }
+            }
+            OpCode::GetGlobal => {
+                let name = name_constant(chunk, short(0));
+                let value = closure.module.globals.borrow().get(name);
+                match value {
+                    Some(value) => self.stack.push(value),
+                    None => return Err(self.undefined(chunk, start, name)),
+                }
+            }
+            OpCode::SetGlobal => {
+                let name = name_constant(chunk, short(0));
+                let value = self.peek(0).clone();
+                if !closure.module.globals.borrow_mut().assign(name, value) {
+                    return Err(self.undefined(chunk, start, name));
+                }
+            }
+            OpCode::DefineGlobal => {
+                let name = name_constant(chunk, short(0));
+                let value = self.pop();
+                closure.module.globals.borrow_mut().define(name, value);
+            }
+            OpCode::CloseUpvalue => {
+                self.close_upvalues(self.stack.len() - 1);
+                self.pop();
+            }
+            OpCode::Negate | OpCode::Not => {
+                let right = self.pop();
+                let result = match (op, right) {
+                    (OpCode::Negate, LiteralValue::Float(value)) => Ok(LiteralValue::Float(-value)),
+                    (_, right) => operations::unary(operator(op), right),
+                };
+                let value = result.map_err(|failure| self.fail(chunk.site(start), failure))?;
+                self.stack.push(value);
+            }
+            OpCode::Add
+            | OpCode::Subtract
+            | OpCode::Multiply
+            | OpCode::Divide
+            | OpCode::Modulo
+            | OpCode::FloorDivide
+            | OpCode::Power
+            | OpCode::Equal
+            | OpCode::NotEqual
+            | OpCode::Greater
+            | OpCode::GreaterEqual
+            | OpCode::Less
+            | OpCode::LessEqual => {
+                let right = self.pop();
+                let left = self.pop();
+                let value = match (op, &left, &right) {
+                    (OpCode::Add, LiteralValue::Float(lv), LiteralValue::Float(rv)) => {
+                        LiteralValue::Float(lv + rv)
+                    }
+                    (OpCode::Subtract, LiteralValue::Float(lv), LiteralValue::Float(rv)) => {
+                        LiteralValue::Float(lv - rv)
+                    }
+                    (OpCode::Multiply, LiteralValue::Float(lv), LiteralValue::Float(rv)) => {
+                        LiteralValue::Float(lv * rv)
+                    }
+                    (OpCode::Less, LiteralValue::Float(lv), LiteralValue::Float(rv)) => {
+                        LiteralValue::Boolean(lv < rv)
+                    }
+                    (OpCode::Greater, LiteralValue::Float(lv), LiteralValue::Float(rv)) => {
+                        LiteralValue::Boolean(lv > rv)
+                    }
+                    //The site's token is only where errors are reported
+                    _ => {
+                        let site = chunk.site(start);
+                        operations::binary(operator(op), site, left, right, self.checked_division)
+                            .map_err(|failure| self.fail(site, failure))?
+                    }
+                };
+                self.stack.push(value);
+            }
+            OpCode::Print => {
+                let value = self.pop();
+                println!("{}", value);
+            }
+            OpCode::Jump => self.frame_mut().ip += short(0),
+            OpCode::JumpIfFalse => {
+                if !operations::is_truthy(self.peek(0)) {
+                    self.frame_mut().ip += short(0);
+                }
+            }
+            OpCode::JumpIfTrue => {
+                if operations::is_truthy(self.peek(0)) {
+                    self.frame_mut().ip += short(0);
+                }
+            }
+            OpCode::Loop => self.frame_mut().ip -= short(0),
+            OpCode::JumpIfBound => {
+                let slot = self.frame().base + byte(0) as usize;
+                if !matches!(self.stack[slot], LiteralValue::None) {
+                    self.frame_mut().ip += short(1);
+                }
+            }
+            OpCode::Call | OpCode::TailCall => {
+                let tail = op == OpCode::TailCall;
+                return self.call(byte(0) as usize, &[], start, tail);
+            }
+            OpCode::CallNamed | OpCode::TailCallNamed => {
+                let names = match &chunk.constants[short(1)] {
+                    Constant::Names(names) => names.clone(),
+                    _ => unreachable!("named calls refer to their names"),
+                };
+                let tail = op == OpCode::TailCallNamed;
+                return self.call(byte(0) as usize, &names, start, tail);
+            }
+            OpCode::Closure => {
+                let function = match &chunk.constants[short(0)] {
+                    Constant::Function(function) => function.clone(),
+                    _ => unreachable!("Closure refers to a function"),
+                };
+                let base = self.frame().base;
+                let mut upvalues = Vec::with_capacity(function.upvalues);
+                for index in 0..function.upvalues {
+                    let is_local = byte(2 + index * 2) == 1;
+                    let slot = byte(3 + index * 2) as usize;
+                    upvalues.push(match is_local {
+                        true => self.capture_upvalue(base + slot),
+                        false => closure.upvalues[slot].clone(),
+                    });
+                }
+                self.frame_mut().ip += function.upvalues * 2;
+                self.stack.push(LiteralValue::Closure(Rc::new(Closure {
+                    function,
+                    upvalues,
+                    module: closure.module.clone(),
+                })));
+            }
+            OpCode::Return => {
+                let result = self.pop();
+                return Ok(self.return_from_frame(result));
+            }
+            OpCode::List => {
+                let elements = self.stack.split_off(self.stack.len() - short(0));
+                self.stack.push(LiteralValue::list(elements));
+            }
+            OpCode::Map => {
+                let values = self.stack.split_off(self.stack.len() - short(0) * 2);
+                let mut map = LoxMap::new();
+                let mut values = values.into_iter();
+                while let (Some(key), Some(value)) = (values.next(), values.next()) {
+                    match MapKey::from_value(&key) {
+                        Ok(key) => map.insert(key, value),
+                        Err(message) => {
+                            return Err(self.error(ErrorKind::Type, chunk.site(start), message))
+                        }
+                    }
+                }
+                self.stack.push(LiteralValue::map(map));
+            }
+            OpCode::Index => {
+                let index = self.pop();
+                let object = self.pop();
+                let value = operations::index(object, &index)
+                    .map_err(|failure| self.fail(chunk.site(start), failure))?;
+                self.stack.push(value);
+            }
+            OpCode::SetIndex => {
+                let value = self.pop();
+                let index = self.pop();
+                let object = self.pop();
+                let value = operations::set_index(object, &index, value)
+                    .map_err(|failure| self.fail(chunk.site(start), failure))?;
+                self.stack.push(value);
+            }
+            OpCode::GetMember => {
+                let object = self.pop();
+                let value = operations::get_member(&object, name_constant(chunk, short(0)))
+                    .map_err(|failure| self.fail(chunk.site(start), failure))?;
+                self.stack.push(value);
+            }
+            OpCode::Interpolate => {
+                let parts = self.stack.split_off(self.stack.len() - short(0));
+                let text: String = parts.iter().map(|part| part.to_string()).collect();
+                self.stack.push(LiteralValue::String(text));
+            }
+            OpCode::IterStart => {
+                let iterable = self.pop();
+                let iterator = LoxIterator::new(&iterable)
+                    .map_err(|message| self.error(ErrorKind::Type, chunk.site(start), message))?;
+                self.stack
+                    .push(LiteralValue::Iterator(Rc::new(RefCell::new(iterator))));
+            }
+            OpCode::IterNext => {
+                let next = match self.peek(0) {
+                    LiteralValue::Iterator(iterator) => iterator.borrow_mut().next(),
+                    _ => unreachable!("IterNext runs on the iterator IterStart made"),
+                };
+                match next {
+                    Some(element) => self.stack.push(element),
+                    None => self.frame_mut().ip += short(0),
+                }
+            }
+            OpCode::PushHandler => {
+                let handler = Handler {
+                    frame: self.frames.len() - 1,
+                    stack: self.stack.len(),
+                    target: self.frame().ip + short(0),
+                };
+                self.handlers.push(handler);
+            }
+            OpCode::PopHandler => {
+                self.handlers.pop();
+            }
+            OpCode::Throw => {
+                let keyword = chunk.site(start);
+                let value = match self.pop() {
+                    //error() values learn where they were thrown
+                    LiteralValue::Error(error) if error.token.is_none() => {
+                        let mut error = error.as_ref().clone();
+                        error.token = Some(keyword.clone());
+                        LiteralValue::Error(Rc::new(error))
+                    }
+                    value => value,
+                };
+                return Err(Box::new(Thrown {
+                    value,
+                    token: keyword.clone(),
+                    trace: self.trace(),
+                }));
+            }
+            OpCode::Rethrow => {
+                let slot = byte(0) as usize;
+                let frame = self.frame_mut();
+                let position = frame
+                    .caught
+                    .iter()
+                    .rposition(|(caught, _)| *caught == slot)
+                    .expect("a finally rethrows what its handler caught");
+                return Err(frame.caught.remove(position).1);
+            }
+            OpCode::Import => {
+                let path = chunk.site(start);
+                return self.import(&closure, start, path);
+            }
+            OpCode::ImportName => {
+                let name = name_constant(chunk, short(0));
+                let value = match self.peek(0) {
+                    LiteralValue::Namespace(namespace) => match namespace.get(name) {
+                        Some(value) => value,
+                        None => {
+                            let message =
+                                format!("Module '{}' has no export '{}'", namespace.name, name);
+                            return Err(self.error(ErrorKind::Import, chunk.site(start), message));
+                        }
+                    },
+                    _ => unreachable!("ImportName runs on the namespace Import left"),
+                };
+                self.stack.push(value);
+            }
+        }
+        Ok(Step::Next)
+    }
+
+    //Calls the value below `positional` arguments and then one value per name
+    fn call(
+        &mut self,
+        positional: usize,
+        names: &[String],
+        start: usize,
+        tail: bool,
+    ) -> Result<Step, Box<Thrown>> {
+        let callee_slot = self.stack.len() - positional - names.len() - 1;
+        let closure = match &self.stack[callee_slot] {
+            LiteralValue::Closure(closure) => closure.clone(),
+            _ => return self.call_native(callee_slot, names, start, tail),
+        };
+        let function = &closure.function;
+        let plain = names.is_empty()
+            && positional == function.params.len()
+            && function.takes_plain_arguments();
+        if !plain {
+            let named_values = self.stack.split_off(callee_slot + 1 + positional);
+            let arguments = self.stack.split_off(callee_slot + 1);
+            let named = names.iter().map(String::as_str).zip(named_values).collect();
+            let bound = lox_function::bind_arguments(
+                &function.display_name(),
+                &function.params,
+                arguments,
+                named,
+            )
+            .map_err(|error| self.error(ErrorKind::Type, self.site(start), error.message))?;
+            //None marks a parameter whose default the function fills in
+            self.stack.extend(
+                bound
+                    .into_iter()
+                    .map(|value| value.unwrap_or(LiteralValue::None)),
+            );
+        }
+
+        let caller = self.frame().closure.clone();
+        if tail {
+            //Takes over the frame of the function making the call
+            let base = self.frame().base;
+            self.close_upvalues(base);
+            let call = self.stack.split_off(callee_slot);
+            self.stack.truncate(base);
+            self.stack.extend(call);
+            let frame = self.frame_mut();
+            frame.closure = closure;
+            frame.ip = 0;
+            frame.caller = Some((caller, start));
+            frame.caught.clear();
+            return Ok(Step::Next);
+        }
+        self.check_depth(start)?;
+        self.frames.push(Frame {
+            closure,
+            ip: 0,
+            base: callee_slot,
+            caller: Some((caller, start)),
+            module: false,
+            caught: Vec::new(),
+        });
+        Ok(Step::Next)
+    }
+
+    //Natives and methods run to completion right away
+    fn call_native(
+        &mut self,
+        callee_slot: usize,
+        names: &[String],
+        start: usize,
+        tail: bool,
+    ) -> Result<Step, Box<Thrown>> {
+        let site = &self.site(start).clone();
+        if let Some(name) = names.first() {
+            let message = format!("Builtins don't take named arguments, got '{}'", name);
+            return Err(self.error(ErrorKind::Type, site, message));
+        }
+        let mut arguments = self.stack.split_off(callee_slot + 1);
+        let (native, receivers) = match self.pop() {
+            LiteralValue::Native(native) => (native, 0),
+            LiteralValue::BoundMethod { receiver, method } => {
+                arguments.insert(0, *receiver);
+                (method, 1)
+            }
+            other => {
+                let message = format!("Can only call functions, got {}", other.type_name());
+                return Err(self.error(ErrorKind::Type, site, message));
+            }
+        };
+        native
+            .check_arity(arguments.len(), receivers)
+            .map_err(|message| self.error(ErrorKind::Type, site, message))?;
+        self.check_depth(start)?;
+        let result = (native.function)(&mut self.natives, arguments).map_err(|message| {
+            //Reported from inside the native, like the tree-walker's frame for it
+            let mut thrown = self.error(ErrorKind::Generic, site, message);
+            let frame = CallFrame::new(format!("{}()", native.name), site.line, self.file());
+            thrown.trace.insert(0, frame);
+            thrown
+        })?;
+        match tail {
+            true => Ok(self.return_from_frame(result)),
+            false => {
+                self.stack.push(result);
+                Ok(Step::Next)
+            }
+        }
+    }
+
+    fn check_depth(&self, start: usize) -> Result<(), Box<Thrown>> {
+        //The script's own frame is not a call
+        if self.frames.len() > self.max_depth {
+            let message = format!(
+                "Stack overflow, calls nested more than {} deep",
+                self.max_depth
+            );
+            return Err(self.error(ErrorKind::Recursion, self.site(start), message));
+        }
+        Ok(())
+    }
+
+    //Leaves the current frame with `result`
+    fn return_from_frame(&mut self, mut result: LiteralValue) -> Step {
+        let frame = self.frames.pop().expect("returning from a frame");
+        self.close_upvalues(frame.base);
+        self.stack.truncate(frame.base);
+        let depth = self.frames.len();
+        while self
+            .handlers
+            .last()
+            .is_some_and(|handler| handler.frame >= depth)
+        {
+            self.handlers.pop();
+        }
+        if frame.module {
+            let path = frame
+                .closure
+                .module
+                .path
+                .clone()
+                .expect("modules come from files");
+            let globals = frame.closure.module.globals.borrow();
+            let namespace = Rc::new(globals.namespace(&modules::module_name(&path)));
+            self.modules.exit(&path, Some(namespace.clone()));
+            result = LiteralValue::Namespace(namespace);
+        }
+        if self.frames.is_empty() {
+            return Step::Done;
+        }
+        self.stack.push(result);
+        Step::Next
+    }
+
+    /// Runs the module named by the path constant once and leaves its
+    /// namespace on the stack. A module not run yet gets a frame of its own
+    /// and its Return leaves the namespace.
+    fn import(
+        &mut self,
+        closure: &Rc<Closure>,
+        start: usize,
+        path: &Token,
+    ) -> Result<Step, Box<Thrown>> {
+        if !self.natives.filesystem {
+            let message =
+                "import needs filesystem access, which this interpreter does not allow".to_string();
+            return Err(self.error(ErrorKind::Import, path, message));
+        }
+        let requested = match &path.literal {
+            LiteralValue::String(requested) => requested,
+            _ => unreachable!("the parser only accepts a string as module path"),
+        };
+        let import_error = |vm: &Self, message| vm.error(ErrorKind::Import, path, message);
+        let resolved = self
+            .modules
+            .resolve(requested, closure.module.path.as_deref())
+            .map_err(|message| import_error(self, message))?;
+        if let Some(namespace) = self.modules.cached(&resolved) {
+            self.stack.push(LiteralValue::Namespace(namespace));
+            return Ok(Step::Next);
+        }
+        self.check_depth(start)?;
+        self.modules
+            .enter(&resolved)
+            .map_err(|message| import_error(self, message))?;
+        let compiled = modules::load(&resolved).and_then(|statements| {
+            compiler::compile(&statements).map_err(|error| {
+                format!(
+                    "Cannot import '{}': {}",
+                    modules::module_name(&resolved),
+                    error.message
+                )
+            })
+        });
+        let function = match compiled {
+            Ok(function) => function,
+            Err(message) => {
+                self.modules.exit(&resolved, None);
+                return Err(import_error(self, message));
+            }
+        };
+        let module = Closure {
+            function: Rc::new(function),
+            upvalues: Vec::new(),
+            module: Rc::new(Module::new(Some(resolved))),
+        };
+        let module = Rc::new(module);
+        let base = self.stack.len();
+        self.stack.push(LiteralValue::Closure(module.clone()));
+        self.frames.push(Frame {
+            closure: module,
+            ip: 0,
+            base,
+            caller: Some((closure.clone(), start)),
+            module: true,
+            caught: Vec::new(),
+        });
+        Ok(Step::Next)
+    }
+
+    //The upvalue for a stack slot, shared by every closure capturing it
+    fn capture_upvalue(&mut self, slot: usize) -> Rc<RefCell<Upvalue>> {
+        let position = self
+            .open_upvalues
+            .partition_point(|upvalue| open_slot(upvalue) < slot);
+        if let Some(existing) = self.open_upvalues.get(position) {
+            if open_slot(existing) == slot {
+                return existing.clone();
+            }
+        }
+        let upvalue = Rc::new(RefCell::new(Upvalue::Open(slot)));
+        self.open_upvalues.insert(position, upvalue.clone());
+        upvalue
+    }
+
+    //Moves the values of slots from `from` on into their upvalues
+    fn close_upvalues(&mut self, from: usize) {
+        let position = self
+            .open_upvalues
+            .partition_point(|upvalue| open_slot(upvalue) < from);
+        for upvalue in self.open_upvalues.drain(position..) {
+            let slot = open_slot(&upvalue);
+            *upvalue.borrow_mut() = Upvalue::Closed(self.stack[slot].clone());
+        }
+    }
+
+    //Unwinds to the innermost handler, or out of the program without one
+    fn throw(&mut self, thrown: Box<Thrown>) -> Result<(), Box<RuntimeError>> {
+        let handler = self.handlers.pop();
+        let depth = handler.as_ref().map_or(0, |handler| handler.frame + 1);
+        while self.frames.len() > depth {
+            let frame = self.frames.pop().expect("unwinding a frame");
+            if frame.module {
+                let path = frame
+                    .closure
+                    .module
+                    .path
+                    .as_deref()
+                    .expect("modules come from files");
+                self.modules.exit(path, None);
+            }
+        }
+        let handler = match handler {
+            Some(handler) => handler,
+            None => {
+                self.close_upvalues(0);
+                self.stack.clear();
+                let token = match &thrown.value {
+                    LiteralValue::Error(error) => error.token.clone().unwrap_or(thrown.token),
+                    _ => thrown.token,
+                };
+                return Err(Box::new(RuntimeError {
+                    value: thrown.value,
+                    token,
+                    trace: thrown.trace,
+                }));
+            }
+        };
+        self.close_upvalues(handler.stack);
+        self.stack.truncate(handler.stack);
+        self.stack.push(thrown.value.clone());
+        let frame = self.frame_mut();
+        let slot = handler.stack - frame.base;
+        frame.ip = handler.target;
+        frame.caught.retain(|(caught, _)| *caught < slot);
+        frame.caught.push((slot, thrown));
+        Ok(())
+    }
+
+    //Token of the instruction at `start` in the current frame
+    fn site(&self, start: usize) -> &Token {
+        self.frame().closure.function.chunk.site(start)
+    }
+
+    //File of the code running now
+    fn file(&self) -> Option<&Path> {
+        self.frame().closure.module.path.as_deref()
+    }
+
+    //The calls in progress, innermost first, as the tree-walker records them
+    fn trace(&self) -> Vec<CallFrame> {
+        self.frames
+            .iter()
+            .rev()
+            .filter_map(|frame| {
+                let (caller, offset) = frame.caller.as_ref()?;
+                let name = match frame.module {
+                    true => {
+                        let path = frame
+                            .closure
+                            .module
+                            .path
+                            .as_deref()
+                            .unwrap_or(Path::new(""));
+                        format!("<module {}>", modules::module_name(path))
+                    }
+                    false => frame.closure.function.display_name(),
+                };
+                let site = caller.function.chunk.site(*offset);
+                Some(CallFrame::new(
+                    name,
+                    site.line,
+                    caller.module.path.as_deref(),
+                ))
+            })
+            .collect()
+    }
+
+    //A builtin runtime error raised at `token`
+    fn error(&self, kind: ErrorKind, token: &Token, message: String) -> Box<Thrown> {
+        let error = LoxError::new(kind.name(), message, Some(token.clone()));
+        Box::new(Thrown {
+            value: LiteralValue::Error(Rc::new(error)),
+            token: token.clone(),
+            trace: self.trace(),
+        })
+    }
+
+    fn fail(&self, token: &Token, (kind, message): operations::Failure) -> Box<Thrown> {
+        self.error(kind, token, message)
+    }
+
+    fn undefined(&self, chunk: &Chunk, start: usize, name: &str) -> Box<Thrown> {
+        let message = format!("Undefined variable {}", name);
+        self.error(ErrorKind::Name, chunk.site(start), message)
+    }
+}
+
+fn name_constant(chunk: &Chunk, index: usize) -> &str {
+    match &chunk.constants[index] {
+        Constant::Value(LiteralValue::String(name)) => name,
+        _ => unreachable!("names are string constants"),
+    }
+}
+
+fn operator(op: OpCode) -> TokenType {
+    op.operator()
+        .expect("only operator instructions apply an operator")
+}
+
+fn open_slot(upvalue: &Rc<RefCell<Upvalue>>) -> usize {
+    match &*upvalue.borrow() {
+        Upvalue::Open(slot) => *slot,
+        Upvalue::Closed(_) => unreachable!("closed upvalues leave the open list"),
+    }
+}
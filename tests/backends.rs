@@ -0,0 +1,65 @@
+//! Runs every program in tests/programs on both backends and checks they
+//! print what the .out file next to it expects, uncaught errors and their
+//! tracebacks included.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+fn programs() -> Vec<PathBuf> {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+    let mut programs: Vec<PathBuf> = fs::read_dir(&directory)
+        .expect("tests/programs exists")
+        .map(|entry| entry.expect("readable directory entry").path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "lox"))
+        .collect();
+    programs.sort();
+    programs
+}
+
+//Run from the program's directory, so imports and tracebacks use the same
+//relative paths on both backends
+fn run(backend: &str, program: &Path) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_lox"))
+        .arg(format!("--backend={}", backend))
+        .arg("--seed=7")
+        .arg(program.file_name().expect("a file"))
+        .current_dir(program.parent().expect("in a directory"))
+        .stdin(Stdio::null())
+        .output()
+        .expect("the lox binary runs");
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    text
+}
+
+#[test]
+fn backends_print_the_expected_output() {
+    let programs = programs();
+    assert!(!programs.is_empty());
+    for program in programs {
+        let expected_path = program.with_extension("out");
+        let expected = fs::read_to_string(&expected_path)
+            .unwrap_or_else(|_| panic!("{} is missing", expected_path.display()));
+        assert!(!expected.is_empty(), "{} is empty", expected_path.display());
+        for backend in ["tree", "vm"] {
+            let output = run(backend, &program);
+            assert!(
+                !output.contains("panicked"),
+                "{} on {}:\n{}",
+                program.display(),
+                backend,
+                output
+            );
+            assert_eq!(
+                output,
+                expected,
+                "{} prints something else on {}",
+                program.display(),
+                backend
+            );
+        }
+    }
+}